@@ -6,6 +6,7 @@ title_session = Canvi de sessió
 action_reset = Reinicialitza
 action_command = Canvia l'ordre
 action_session = Tria una sessió
+action_layout = Teclat
 action_power = Engegada
 
 date = %a %d %h %Y - %H:%M
@@ -20,4 +21,7 @@ shutdown = Atura
 reboot = Reinicia
 
 status_command = CMD
+status_layout = TECLAT
 status_caps = BLOQ MAJ
+status_num = BLOQ NUM
+status_scroll = BLOQ DESPL
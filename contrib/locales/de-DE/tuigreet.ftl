@@ -6,6 +6,7 @@ title_session = Sitzung ändern
 action_reset = Zurücksetzen
 action_command = Befehl ändern
 action_session = Sitzung auswählen
+action_layout = Tastatur
 action_power = Energie
 
 date = %a, %d %h %Y - %H:%M
@@ -20,4 +21,7 @@ shutdown = Herunterfahren
 reboot = Neustart
 
 status_command = CMD
+status_layout = TASTATUR
 status_caps = FESTSTELLTASTE
+status_num = NUM
+status_scroll = ROLLEN
@@ -6,6 +6,7 @@ title_users = User
 
 action_command = Command
 action_session = Session
+action_layout = Layout
 action_power = Power
 
 date = %a, %d %h %Y
@@ -22,6 +23,11 @@ command_missing = No command configured
 command_exited = Command exited with
 command_failed = Command failed
 
+layout_failed = Could not switch keyboard layout
+
 status_command = COMMAND
 status_session = SESSION
+status_layout = LAYOUT
 status_caps = CAPS LOCK
+status_num = NUM LOCK
+status_scroll = SCROLL LOCK
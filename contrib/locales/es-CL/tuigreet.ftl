@@ -7,6 +7,7 @@ title_users = Seleccionar usuario
 action_reset = Reiniciar
 action_command = Cambiar comando
 action_session = Escoger sesión
+action_layout = Teclado
 action_power = Energía
 
 date = %a, %d %h %Y - %H:%M
@@ -25,4 +26,7 @@ command_exited = El comando terminó con
 command_failed = El comando falló
 
 status_command = CMD
+status_layout = TECLADO
 status_caps = BLOQ MAYÚS
+status_num = BLOQ NUM
+status_scroll = BLOQ DESPL
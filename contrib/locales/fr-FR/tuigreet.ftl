@@ -7,6 +7,7 @@ title_users = Choisissez un utilisateur
 action_reset = Réinitialiser
 action_command = Changer la commande
 action_session = Choisir la session
+action_layout = Clavier
 action_power = Alimentation
 
 date = %a %d %h %Y - %H:%M
@@ -26,4 +27,7 @@ command_exited = La commande a retourné
 command_failed = Échec de la commande
 
 status_command = CMD
+status_layout = CLAVIER
 status_caps = VERR. MAJ.
+status_num = VERR. NUM.
+status_scroll = ARRÊT DÉFIL.
@@ -6,6 +6,7 @@ title_session = Cambia sessione
 action_reset = Reset
 action_command = Cambia comando
 action_session = Scegli sessione
+action_layout = Tastiera
 action_power = Alimentazione
 
 date = %a, %d %h %Y - %H:%M
@@ -20,4 +21,7 @@ shutdown = Spegni
 reboot = Riavvia
 
 status_command = CMD
+status_layout = TASTIERA
 status_caps = BLC MAIUSC
+status_num = BLC NUM
+status_scroll = BLC SCORR
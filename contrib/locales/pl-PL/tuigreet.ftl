@@ -8,6 +8,7 @@ title_users = Wybór użytkownika
 action_reset = Reset
 action_command = Zmiana polecenia
 action_session = Wybór sesji
+action_layout = Układ
 action_power = Zasilanie
 
 date = %a, %d %h %Y - %H:%M
@@ -26,4 +27,7 @@ command_exited = Polecenie zakończone z kodem
 command_failed = Polecenie zakończone niepowodzeniem
 
 status_command = CMD
+status_layout = UKŁAD
 status_caps = CAPS LOCK
+status_num = NUM LOCK
+status_scroll = SCROLL LOCK
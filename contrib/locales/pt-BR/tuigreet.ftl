@@ -6,6 +6,7 @@ title_session = Mudar sessão
 action_reset = Reset
 action_command = Mudar comando
 action_session = Escolher sessão
+action_layout = Teclado
 action_power = Energia
 
 date = %a, %d %h %Y - %H:%M
@@ -19,4 +20,7 @@ shutdown = Desligar
 reboot = Reiniciar
 
 status_command = CMD
+status_layout = TECLADO
 status_caps = CAPS LOCK
+status_num = NUM LOCK
+status_scroll = SCROLL LOCK
@@ -7,6 +7,7 @@ title_users = Выбрать пользователя
 action_reset = Перезагрузка
 action_command = Изменить команду
 action_session = Выбрать сеанс
+action_layout = Раскладка
 action_power = Питание
 
 date = %a, %d %h %Y - %H:%M
@@ -25,4 +26,7 @@ command_exited = Команда завершилась с
 command_failed = Команда не выполнена
 
 status_command = CMD
+status_layout = РАСКЛАДКА
 status_caps = CAPS LOCK
+status_num = NUM LOCK
+status_scroll = SCROLL LOCK
@@ -7,6 +7,7 @@ title_users = Виберіть користувача
 action_reset = Скинути
 action_command = Змінити команду
 action_session = Вибрати сесію
+action_layout = Розкладка
 action_power = Живлення
 
 date = %a, %d %h %Y - %H:%M
@@ -25,4 +26,7 @@ command_exited = Команда завершила роботу з кодом
 command_failed = Під час виконання команди виникла помилка
 
 status_command = CMD
+status_layout = РОЗКЛАДКА
 status_caps = CAPS LOCK
+status_num = NUM LOCK
+status_scroll = SCROLL LOCK
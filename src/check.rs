@@ -0,0 +1,222 @@
+use std::{
+  collections::HashSet,
+  fmt::{self, Display},
+  fs,
+  path::Path,
+};
+
+use nix::unistd::{access, AccessFlags};
+
+use crate::{
+  fortune::FortuneSource,
+  info::{find_executable, get_session_paths, load_desktop_file, CACHE_DIR},
+  settings::DEFAULT_XSESSION_WRAPPER,
+  ui::sessions::SessionType,
+  Greeter,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Severity {
+  Ok,
+  Warning,
+  Error,
+}
+
+impl Display for Severity {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Severity::Ok => f.pad("ok"),
+      Severity::Warning => f.pad("warning"),
+      Severity::Error => f.pad("error"),
+    }
+  }
+}
+
+#[derive(Default)]
+struct Report {
+  entries: Vec<(Severity, String)>,
+}
+
+impl Report {
+  fn push<S>(&mut self, severity: Severity, message: S)
+  where
+    S: Into<String>,
+  {
+    self.entries.push((severity, message.into()));
+  }
+
+  fn count(&self, severity: Severity) -> usize {
+    self.entries.iter().filter(|(entry, _)| *entry == severity).count()
+  }
+}
+
+// Validates the configuration and the environment tuigreet will run in,
+// prints a report and returns the exit code the process should use.
+//
+// This is run instead of the greeter when `--check-config` is given, after
+// options have been successfully parsed, so it does not need to reach `greetd`.
+pub fn run(greeter: &Greeter) -> i32 {
+  let mut report = Report::default();
+
+  match greeter.settings.config_file {
+    Some(ref path) => report.push(Severity::Ok, format!("configuration file {} is valid", path.display())),
+    None => report.push(Severity::Ok, "command line options are valid"),
+  }
+
+  report.push(Severity::Ok, format!("theme colors are displayed with {}", greeter.settings.color_depth));
+
+  let x11_sessions = check_sessions(greeter, &mut report);
+
+  if let Some(ref wrapper) = greeter.settings.session_wrapper {
+    check_command(&mut report, "session wrapper", wrapper, Severity::Error);
+  }
+
+  if let Some(ref wrapper) = greeter.settings.xsession_wrapper {
+    let severity = match (x11_sessions, wrapper.as_str()) {
+      (0, _) | (_, DEFAULT_XSESSION_WRAPPER) => Severity::Warning,
+      _ => Severity::Error,
+    };
+
+    check_command(&mut report, "X11 session wrapper", wrapper, severity);
+  }
+
+  if let Some((ref command, _)) = greeter.settings.command {
+    check_command(&mut report, "default command", command, Severity::Warning);
+  }
+
+  if greeter.settings.power_setsid {
+    check_command(&mut report, "power command prefix", "setsid", Severity::Error);
+  }
+
+  if !greeter.settings.keyboard_layouts.is_empty() {
+    check_command(&mut report, "keyboard layout command", "loadkeys", Severity::Error);
+  }
+
+  for (name, command) in [
+    ("shutdown", &greeter.settings.power_shutdown),
+    ("reboot", &greeter.settings.power_reboot),
+  ] {
+    match command {
+      Some(command) => check_command(&mut report, &format!("{name} command"), command, Severity::Error),
+      None => check_command(&mut report, &format!("{name} command"), "shutdown", Severity::Warning),
+    }
+  }
+
+  if let Some(ref command) = greeter.settings.widget_command {
+    check_command(&mut report, "widget command", command, Severity::Error);
+  }
+
+  match greeter.settings.fortune {
+    Some(FortuneSource::Command(ref command)) => check_command(&mut report, "fortune command", command, Severity::Error),
+    Some(ref source @ (FortuneSource::Strfile(ref path) | FortuneSource::File(ref path) | FortuneSource::Directory(ref path))) => {
+      match path.exists() {
+        true => report.push(Severity::Ok, format!("fortune source {source} exists")),
+        false => report.push(Severity::Error, format!("fortune source {source} does not exist")),
+      }
+    }
+
+    None => report.push(Severity::Warning, "no fortune database was found"),
+  }
+
+  check_cache(greeter, &mut report);
+
+  for (severity, message) in &report.entries {
+    println!("{severity:>7}  {message}");
+  }
+
+  let (errors, warnings) = (report.count(Severity::Error), report.count(Severity::Warning));
+
+  println!();
+  println!("{errors} error(s), {warnings} warning(s)");
+
+  match errors {
+    0 => 0,
+    _ => 1,
+  }
+}
+
+// Checks every session directory and desktop file, and returns the number of
+// valid X11 sessions found.
+fn check_sessions(greeter: &Greeter, report: &mut Report) -> usize {
+  let explicit = !greeter.settings.session_paths.is_empty();
+  let locale = greeter.locale.to_string();
+  let mut seen = HashSet::new();
+  let mut sessions = 0;
+  let mut x11_sessions = 0;
+
+  for (path, session_type) in get_session_paths(greeter) {
+    let entries = match fs::read_dir(path) {
+      Ok(entries) => entries,
+
+      Err(err) => {
+        let severity = if explicit { Severity::Error } else { Severity::Warning };
+        report.push(severity, format!("session directory {}: {err}", path.display()));
+
+        continue;
+      }
+    };
+
+    for entry in entries.flatten() {
+      let path = entry.path();
+
+      if !seen.insert((*session_type, entry.file_name())) {
+        report.push(Severity::Ok, format!("session file {} is shadowed by an earlier one", path.display()));
+        continue;
+      }
+
+      match load_desktop_file(&path, *session_type, &locale) {
+        Ok(Some(session)) => {
+          sessions += 1;
+
+          if *session_type == SessionType::X11 {
+            x11_sessions += 1;
+          }
+
+          report.push(Severity::Ok, format!("session '{}' from {}", session.name, path.display()));
+        }
+
+        Ok(None) => report.push(Severity::Ok, format!("session file {} is hidden", path.display())),
+        Err(err) => report.push(Severity::Error, format!("session file {}: {err}", path.display())),
+      }
+    }
+  }
+
+  if sessions == 0 && greeter.settings.command.is_none() {
+    report.push(Severity::Warning, "no session was found and no default command is set");
+  }
+
+  x11_sessions
+}
+
+// Checks that the executable of a command line can be found, splitting it in
+// words the way it is split when it runs.
+fn check_command(report: &mut Report, label: &str, command: &str, severity: Severity) {
+  let words = match shell_words::split(command) {
+    Ok(words) => words,
+    Err(err) => return report.push(severity, format!("{label} could not be parsed: {err}")),
+  };
+
+  match words.iter().find(|word| !word.contains('=')) {
+    Some(executable) => match find_executable(executable) {
+      Some(path) => report.push(Severity::Ok, format!("{label} '{executable}' found at {}", path.display())),
+      None => report.push(severity, format!("{label} '{executable}' was not found in PATH")),
+    },
+
+    None => report.push(severity, format!("{label} is empty")),
+  }
+}
+
+fn check_cache(greeter: &Greeter, report: &mut Report) {
+  let settings = &greeter.settings;
+  let remembers = settings.remember || settings.remember_session || settings.remember_user_session;
+
+  let severity = match remembers || !settings.keyboard_layouts.is_empty() {
+    true => Severity::Error,
+    false => Severity::Warning,
+  };
+
+  match access(Path::new(CACHE_DIR), AccessFlags::W_OK) {
+    Ok(_) => report.push(Severity::Ok, format!("cache directory {CACHE_DIR} is writable")),
+    Err(err) => report.push(severity, format!("cache directory {CACHE_DIR} is not writable: {err}")),
+  }
+}
@@ -0,0 +1,356 @@
+use std::{
+  collections::BTreeSet,
+  env,
+  ffi::OsStr,
+  fs,
+  path::{Path, PathBuf},
+};
+
+use nix::unistd::{access, AccessFlags};
+
+use crate::ui::sessions::Session;
+
+// Number of directory entries read at most when completing a word, so a large
+// search path or directory does not block the prompt for long.
+const SCAN_LIMIT: usize = 2048;
+
+// A possible replacement for part of the command being edited.
+#[derive(Clone)]
+pub struct Candidate {
+  // Text inserted in place of the completed part of the command.
+  pub value: String,
+  // Text displayed in the dropdown.
+  pub label: String,
+  // Byte range of the original command replaced by the candidate.
+  start: usize,
+  end: usize,
+}
+
+// Candidates offered in the command prompt, and the one currently applied.
+#[derive(Default)]
+pub struct Completion {
+  pub candidates: Vec<Candidate>,
+  pub selected: Option<usize>,
+  // Command as it was typed before a candidate was applied, and the position
+  // of the cursor within it.
+  original: String,
+  cursor: usize,
+}
+
+impl Completion {
+  pub fn clear(&mut self) {
+    self.candidates.clear();
+    self.selected = None;
+    self.original.clear();
+    self.cursor = 0;
+  }
+
+  // Lists the sessions whose command contains what was typed, as the user
+  // types it.
+  pub fn suggest(&mut self, command: &str, cursor: usize, sessions: &[Session]) {
+    self.clear();
+    self.candidates = session_candidates(command, sessions);
+    self.original = command.to_string();
+    self.cursor = cursor;
+  }
+
+  // Lists the sessions matching the command, followed by the executables or
+  // paths the word before the cursor can be completed to, `~` standing for the
+  // provided home directory.
+  pub fn complete(&mut self, command: &str, cursor: usize, sessions: &[Session], home: Option<&Path>) {
+    self.suggest(command, cursor, sessions);
+    self.candidates.extend(word_candidates(command, cursor, home));
+  }
+
+  // Selects the next or previous candidate, wrapping around, and returns the
+  // command with the candidate applied, along with the new cursor position.
+  pub fn cycle(&mut self, forward: bool) -> Option<(String, usize)> {
+    let count = self.candidates.len();
+
+    let selected = match (self.selected, forward) {
+      _ if count == 0 => return None,
+      (None, true) => 0,
+      (None, false) => count - 1,
+      (Some(index), true) => (index + 1) % count,
+      (Some(index), false) => (index + count - 1) % count,
+    };
+
+    self.selected = Some(selected);
+
+    Some(self.apply(selected))
+  }
+
+  // Returns the command as it was before any candidate was applied.
+  pub fn revert(&mut self) -> (String, usize) {
+    let original = (self.original.clone(), self.cursor);
+
+    self.selected = None;
+
+    original
+  }
+
+  fn apply(&self, index: usize) -> (String, usize) {
+    let candidate = &self.candidates[index];
+    let command = format!("{}{}{}", &self.original[..candidate.start], candidate.value, &self.original[candidate.end..]);
+
+    (command, candidate.start + candidate.value.len())
+  }
+}
+
+fn session_candidates(command: &str, sessions: &[Session]) -> Vec<Candidate> {
+  let typed = command.trim().to_lowercase();
+
+  sessions
+    .iter()
+    .filter(|session| session.command != command && session.command.to_lowercase().contains(&typed))
+    .map(|session| Candidate {
+      value: session.command.clone(),
+      label: format!("{} ({})", session.command, session.name),
+      start: 0,
+      end: command.len(),
+    })
+    .collect()
+}
+
+// Completes the word before the cursor, as an executable from `PATH` if it is
+// the first word of the command, or as a path otherwise. Completed names are
+// followed by a space, unless one already follows the cursor.
+fn word_candidates(command: &str, cursor: usize, home: Option<&Path>) -> Vec<Candidate> {
+  let start = command[..cursor]
+    .char_indices()
+    .rev()
+    .find(|(_, c)| c.is_whitespace())
+    .map(|(index, c)| index + c.len_utf8())
+    .unwrap_or(0);
+
+  let word = &command[start..cursor];
+
+  let completions = if command[..start].trim().is_empty() && !word.contains('/') {
+    env::var_os("PATH").map(|path| executables(word, &path)).unwrap_or_default()
+  } else {
+    paths(word, home)
+  };
+
+  let spaced = command[cursor..].starts_with(char::is_whitespace);
+
+  completions
+    .into_iter()
+    .map(|(value, label)| Candidate {
+      value: match value.ends_with('/') || spaced {
+        true => value,
+        false => format!("{value} "),
+      },
+      label,
+      start,
+      end: cursor,
+    })
+    .collect()
+}
+
+// Lists the executables from a search path whose name starts with a prefix.
+// Nothing is listed until the name is started, since scanning every directory
+// of the search path would block the prompt.
+fn executables(prefix: &str, path: &OsStr) -> Vec<(String, String)> {
+  if prefix.is_empty() {
+    return vec![];
+  }
+
+  let names: BTreeSet<String> = env::split_paths(path)
+    .filter_map(|dir| fs::read_dir(dir).ok())
+    .flatten()
+    .flatten()
+    .take(SCAN_LIMIT)
+    .map(|entry| (entry.file_name().to_string_lossy().to_string(), entry.path()))
+    .filter(|(name, path)| name.starts_with(prefix) && is_executable(path))
+    .map(|(name, _)| name)
+    .collect();
+
+  names.into_iter().map(|name| (name.clone(), name)).collect()
+}
+
+// Lists the entries of a directory whose name starts with the end of a path.
+// Only absolute paths and paths from the home directory are completed, since
+// the working directory of the greeter is not the one the command starts in.
+fn paths(word: &str, home: Option<&Path>) -> Vec<(String, String)> {
+  let (dir, prefix) = match word.rfind('/') {
+    Some(index) => word.split_at(index + 1),
+    None => return vec![],
+  };
+
+  let location = match dir.strip_prefix("~/") {
+    Some(relative) => match home {
+      Some(home) => home.join(relative),
+      None => return vec![],
+    },
+    None if dir.starts_with('/') => PathBuf::from(dir),
+    None => return vec![],
+  };
+
+  let Ok(entries) = fs::read_dir(location) else {
+    return vec![];
+  };
+
+  let mut names: Vec<(String, String)> = entries
+    .flatten()
+    .take(SCAN_LIMIT)
+    .filter_map(|entry| {
+      let name = entry.file_name().to_string_lossy().to_string();
+
+      // Hidden files are only offered when their name was started.
+      if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+        return None;
+      }
+
+      match entry.path().is_dir() {
+        true => Some((format!("{dir}{name}/"), format!("{name}/"))),
+        false => Some((format!("{dir}{name}"), name)),
+      }
+    })
+    .collect();
+
+  names.sort();
+  names
+}
+
+fn is_executable(path: &Path) -> bool {
+  path.is_file() && access(path, AccessFlags::X_OK).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    env,
+    fs::{self, File},
+    os::unix::fs::PermissionsExt,
+  };
+
+  use tempfile::TempDir;
+
+  use super::{executables, paths, Completion};
+  use crate::ui::sessions::Session;
+
+  fn session(name: &str, command: &str) -> Session {
+    Session {
+      name: name.to_string(),
+      command: command.to_string(),
+      ..Default::default()
+    }
+  }
+
+  // Creates files in a temporary directory, executable if their name starts
+  // with `x`, and directories if it ends with a slash.
+  fn tree(names: &[&str]) -> TempDir {
+    let dir = TempDir::new().unwrap();
+
+    for name in names {
+      let path = dir.path().join(name);
+
+      match name.ends_with('/') {
+        true => fs::create_dir(path).unwrap(),
+        false => {
+          let file = File::create(&path).unwrap();
+          let mode = if name.starts_with('x') { 0o755 } else { 0o644 };
+
+          file.set_permissions(fs::Permissions::from_mode(mode)).unwrap();
+        }
+      }
+    }
+
+    dir
+  }
+
+  #[test]
+  fn sessions_matching_the_command() {
+    let sessions = [session("Sway", "sway"), session("Plasma", "startplasma-wayland"), session("X", "startx")];
+    let mut completion = Completion::default();
+
+    completion.suggest("START", 5, &sessions);
+
+    let labels: Vec<&str> = completion.candidates.iter().map(|candidate| candidate.label.as_str()).collect();
+    assert_eq!(labels, ["startplasma-wayland (Plasma)", "startx (X)"]);
+
+    // A command that was typed in full is not offered again.
+    completion.suggest("sway", 4, &sessions);
+    assert!(completion.candidates.is_empty());
+  }
+
+  #[test]
+  fn cycling_and_reverting() {
+    let sessions = [session("Sway", "sway --unsupported-gpu"), session("X", "startx")];
+    let mut completion = Completion::default();
+
+    completion.suggest("s", 1, &sessions);
+
+    assert_eq!(completion.cycle(true), Some(("sway --unsupported-gpu".to_string(), 22)));
+    assert_eq!(completion.cycle(true), Some(("startx".to_string(), 6)));
+    assert_eq!(completion.cycle(true), Some(("sway --unsupported-gpu".to_string(), 22)));
+    assert_eq!(completion.cycle(false), Some(("startx".to_string(), 6)));
+    assert_eq!(completion.revert(), ("s".to_string(), 1));
+    assert_eq!(completion.selected, None);
+    assert_eq!(completion.cycle(false), Some(("startx".to_string(), 6)));
+
+    completion.clear();
+    assert_eq!(completion.cycle(true), None);
+  }
+
+  #[test]
+  fn executables_from_the_search_path() {
+    let first = tree(&["xsway", "xstart", "sway-notes", "xdir/"]);
+    let second = tree(&["xsway", "xswaybar"]);
+    let path = env::join_paths([first.path(), second.path()]).unwrap();
+
+    let names: Vec<String> = executables("xsw", &path).into_iter().map(|(_, name)| name).collect();
+    assert_eq!(names, ["xsway", "xswaybar"]);
+
+    assert_eq!(executables("xst", &path), [("xstart".to_string(), "xstart".to_string())]);
+    assert!(executables("", &path).is_empty());
+  }
+
+  #[test]
+  fn paths_in_a_directory() {
+    let dir = tree(&["notes", "nested/", ".hidden", "other"]);
+    let prefix = format!("{}/", dir.path().display());
+
+    assert_eq!(paths(&format!("{prefix}n"), None), [
+      (format!("{prefix}nested/"), "nested/".to_string()),
+      (format!("{prefix}notes"), "notes".to_string()),
+    ]);
+
+    assert_eq!(paths(&prefix, None).len(), 3);
+    assert_eq!(paths(&format!("{prefix}."), None), [(format!("{prefix}.hidden"), ".hidden".to_string())]);
+    assert!(paths(&format!("{prefix}missing/"), None).is_empty());
+
+    // Paths from the home directory keep their `~`, and relative paths are not
+    // completed.
+    assert_eq!(paths("~/nes", Some(dir.path())), [("~/nested/".to_string(), "nested/".to_string())]);
+    assert!(paths("~/nes", None).is_empty());
+    assert!(paths("nes", None).is_empty());
+    assert!(paths("./nes", None).is_empty());
+  }
+
+  #[test]
+  fn completing_words() {
+    let dir = tree(&["xsession"]);
+    let sessions = [session("Sway", "sway")];
+    let mut completion = Completion::default();
+
+    // Only the word before the cursor is replaced.
+    let command = format!("sway --config {}/xs --debug", dir.path().display());
+    let cursor = command.find(" --debug").unwrap();
+
+    completion.complete(&command, cursor, &sessions, None);
+
+    assert_eq!(completion.candidates.len(), 1);
+    assert_eq!(
+      completion.cycle(true),
+      Some((format!("sway --config {}/xsession --debug", dir.path().display()), cursor + 6))
+    );
+
+    // A space is added after a name completed at the end of the command.
+    let command = format!("sway --config {}/xs", dir.path().display());
+
+    completion.complete(&command, command.len(), &sessions, None);
+
+    assert_eq!(completion.cycle(true), Some((format!("{command}ession "), command.len() + 7)));
+  }
+}
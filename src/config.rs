@@ -0,0 +1,323 @@
+use std::{
+  collections::HashMap,
+  env,
+  error::Error,
+  fs,
+  path::{Path, PathBuf},
+};
+
+use getopts::Matches;
+use toml::{Table, Value};
+
+pub const DEFAULT_CONFIG_FILE: &str = "/etc/tuigreet/config.toml";
+
+// Describes how a configuration file key should be interpreted.
+#[derive(Clone, Copy)]
+enum Kind {
+  // Boolean value, mapping to a command line flag.
+  Flag,
+  // Boolean value, mapping to a command line flag with the opposite meaning
+  // (`setsid = false` means `--power-no-setsid`).
+  NegatedFlag,
+  // Either a boolean enabling the option, or a string value for it.
+  FlagOrValue,
+  // String or integer value.
+  Value,
+  // List of strings, mapping to an option that can appear more than once.
+  Multi,
+  // List of paths, or colon-separated string of paths.
+  Paths,
+  // Table of strings or lists of strings, mapping to an option that can appear
+  // more than once with `KEY=VALUE[,VALUE...]` values.
+  Map,
+}
+
+// Maps every command line option to its location in the configuration file.
+const KEYS: &[(&str, &str, Kind)] = &[
+  ("debug", "debug", Kind::FlagOrValue),
+  ("cmd", "cmd", Kind::Value),
+  ("env", "env", Kind::Multi),
+  ("width", "width", Kind::Value),
+  ("issue", "issue", Kind::Flag),
+  ("greeting", "greeting", Kind::Value),
+  ("time", "time", Kind::Flag),
+  ("clock-font", "clock.font", Kind::Value),
+  ("clock-format", "clock.format", Kind::Value),
+  ("clock-12h", "clock.12h", Kind::Flag),
+  ("clock-seconds", "clock.seconds", Kind::Flag),
+  ("date-format", "clock.date-format", Kind::Value),
+  ("fortune", "fortune.source", Kind::Value),
+  ("fortune-timeout", "fortune.timeout", Kind::Value),
+  ("layout", "layout", Kind::Map),
+  ("widget-command", "widgets.command", Kind::Value),
+  ("keyboard-layouts", "keyboard.layouts", Kind::Multi),
+  ("mouse", "mouse", Kind::Flag),
+  ("theme", "theme", Kind::Value),
+  ("color-depth", "color-depth", Kind::Value),
+  ("asterisks", "asterisks", Kind::Flag),
+  ("asterisks-char", "asterisks-char", Kind::Value),
+  ("window-padding", "window-padding", Kind::Value),
+  ("container-padding", "container-padding", Kind::Value),
+  ("prompt-padding", "prompt-padding", Kind::Value),
+  ("remember", "remember.username", Kind::Flag),
+  ("remember-session", "remember.session", Kind::Flag),
+  ("remember-user-session", "remember.user-session", Kind::Flag),
+  ("user-menu", "user-menu.enabled", Kind::Flag),
+  ("user-menu-min-uid", "user-menu.min-uid", Kind::Value),
+  ("user-menu-max-uid", "user-menu.max-uid", Kind::Value),
+  ("sessions", "sessions.wayland", Kind::Paths),
+  ("xsessions", "sessions.x11", Kind::Paths),
+  ("session-wrapper", "sessions.wrapper", Kind::Value),
+  ("xsession-wrapper", "sessions.x11-wrapper", Kind::Value),
+  ("no-xsession-wrapper", "sessions.no-x11-wrapper", Kind::Flag),
+  ("power-shutdown", "power.shutdown", Kind::Value),
+  ("power-reboot", "power.reboot", Kind::Value),
+  ("power-no-setsid", "power.setsid", Kind::NegatedFlag),
+  ("kb-command", "keybindings.command", Kind::Value),
+  ("kb-sessions", "keybindings.sessions", Kind::Value),
+  ("kb-fortune", "keybindings.fortune", Kind::Value),
+  ("kb-layout", "keybindings.layout", Kind::Value),
+  ("kb-power", "keybindings.power", Kind::Value),
+  ("keybinding", "keymap", Kind::Map),
+];
+
+enum FileValue {
+  Flag(bool),
+  Value(String),
+  Multi(Vec<String>),
+}
+
+// Values read from a TOML configuration file, indexed by the name of the
+// command line option they map to.
+struct ConfigFile {
+  path: PathBuf,
+  values: HashMap<&'static str, (&'static str, FileValue)>,
+}
+
+// Layered view of the configuration, from the command line and, optionally, a
+// configuration file. Values from the command line always take precedence.
+pub struct Config {
+  matches: Matches,
+  file: Option<ConfigFile>,
+}
+
+impl Config {
+  // Builds the configuration from parsed command line arguments, loading the
+  // configuration file either from `--config` or from its default location.
+  pub fn new(matches: Matches) -> Result<Config, Box<dyn Error>> {
+    let file = match matches.opt_str("config") {
+      Some(path) => Some(ConfigFile::load(path)?),
+      None if Path::new(DEFAULT_CONFIG_FILE).exists() => Some(ConfigFile::load(DEFAULT_CONFIG_FILE)?),
+      None => None,
+    };
+
+    Ok(Config { matches, file })
+  }
+
+  // Builds the configuration from command line arguments and the content of a
+  // configuration file, which is never read from its default location.
+  #[cfg(test)]
+  pub fn parse(args: &[&str], file: &str) -> Result<Config, Box<dyn Error>> {
+    use std::io::Write;
+
+    let mut config = tempfile::NamedTempFile::new()?;
+    config.write_all(file.as_bytes())?;
+
+    let path = config.path().to_string_lossy().into_owned();
+    let args: Vec<&str> = ["--config", path.as_str()].iter().chain(args).copied().collect();
+
+    Config::new(crate::Greeter::options().parse(args)?)
+  }
+
+  // Parses the provided options and configuration file and loads them, and
+  // returns the error they are expected to be rejected with.
+  #[cfg(test)]
+  pub fn rejection<T, E: ToString>(args: &[&str], file: &str, load: impl FnOnce(&Config) -> Result<T, E>) -> String {
+    let config = match Config::parse(args, file) {
+      Ok(config) => config,
+      Err(err) => return err.to_string(),
+    };
+
+    match load(&config) {
+      Ok(_) => panic!("{:?} and {:?} should be rejected", args, file),
+      Err(err) => err.to_string(),
+    }
+  }
+
+  pub fn path(&self) -> Option<&Path> {
+    self.file.as_ref().map(|file| file.path.as_path())
+  }
+
+  pub fn opt_present(&self, name: &str) -> bool {
+    if self.matches.opt_present(name) {
+      return true;
+    }
+
+    match self.file_value(name) {
+      Some(FileValue::Flag(value)) => *value,
+      Some(_) => true,
+      None => false,
+    }
+  }
+
+  pub fn opt_str(&self, name: &str) -> Option<String> {
+    if self.matches.opt_present(name) {
+      return self.matches.opt_str(name);
+    }
+
+    match self.file_value(name) {
+      Some(FileValue::Value(value)) => Some(value.clone()),
+      _ => None,
+    }
+  }
+
+  pub fn opt_strs(&self, name: &str) -> Vec<String> {
+    if self.matches.opt_present(name) {
+      return self.matches.opt_strs(name);
+    }
+
+    match self.file_value(name) {
+      Some(FileValue::Multi(values)) => values.clone(),
+      Some(FileValue::Value(value)) => vec![value.clone()],
+      _ => vec![],
+    }
+  }
+
+  // Returns a human-readable description of where the value for an option
+  // comes from, to be used in error messages.
+  pub fn origin(&self, name: &str) -> String {
+    if !self.matches.opt_present(name) {
+      if let Some(file) = &self.file {
+        if let Some((key, _)) = file.values.get(name) {
+          return format!("{}: key '{key}'", file.path.display());
+        }
+      }
+    }
+
+    format!("--{name}")
+  }
+
+  fn file_value(&self, name: &str) -> Option<&FileValue> {
+    self.file.as_ref().and_then(|file| file.values.get(name)).map(|(_, value)| value)
+  }
+}
+
+impl ConfigFile {
+  fn load<P>(path: P) -> Result<ConfigFile, Box<dyn Error>>
+  where
+    P: AsRef<Path>,
+  {
+    let path = path.as_ref();
+
+    let contents = fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?;
+    let table = contents
+      .parse::<Table>()
+      .map_err(|err| format!("{}: {}", path.display(), err.message()))?;
+
+    let mut file = ConfigFile {
+      path: path.to_path_buf(),
+      values: HashMap::new(),
+    };
+
+    file.read_table(&table, None)?;
+
+    Ok(file)
+  }
+
+  fn read_table(&mut self, table: &Table, prefix: Option<&str>) -> Result<(), Box<dyn Error>> {
+    for (key, value) in table {
+      let key = match prefix {
+        Some(prefix) => format!("{prefix}.{key}"),
+        None => key.to_string(),
+      };
+
+      let Some(&(name, file_key, kind)) = KEYS.iter().find(|(_, file_key, _)| *file_key == key) else {
+        match value {
+          Value::Table(table) => self.read_table(table, Some(&key))?,
+          _ => return Err(format!("{}: unknown key '{key}'", self.path.display()).into()),
+        }
+
+        continue;
+      };
+
+      let value = match (kind, value) {
+        (Kind::Flag | Kind::FlagOrValue, Value::Boolean(value)) => FileValue::Flag(*value),
+        (Kind::NegatedFlag, Value::Boolean(value)) => FileValue::Flag(!*value),
+        (Kind::Value | Kind::FlagOrValue, Value::String(value)) => FileValue::Value(value.clone()),
+        (Kind::Value, Value::Integer(value)) => FileValue::Value(value.to_string()),
+        (Kind::Multi, Value::Array(values)) => FileValue::Multi(self.read_strings(file_key, values)?),
+        (Kind::Paths, Value::String(value)) => FileValue::Value(value.clone()),
+        (Kind::Paths, Value::Array(values)) => {
+          let paths = env::join_paths(self.read_strings(file_key, values)?)
+            .map_err(|err| format!("{}: key '{file_key}': {err}", self.path.display()))?;
+
+          FileValue::Value(paths.to_string_lossy().to_string())
+        }
+
+        (Kind::Map, Value::Table(table)) => {
+          let mut entries = Vec::new();
+
+          for (name, value) in table {
+            let key = format!("{file_key}.{name}");
+
+            let values = match value {
+              Value::String(value) => vec![value.clone()],
+              Value::Array(values) => self.read_strings(&key, values)?,
+              value => {
+                return Err(
+                  format!(
+                    "{}: key '{key}' must be a string or an array of strings, found {}",
+                    self.path.display(),
+                    value.type_str()
+                  )
+                  .into(),
+                )
+              }
+            };
+
+            match values.is_empty() {
+              true => entries.push(format!("{name}=none")),
+              false => entries.push(format!("{name}={}", values.join(","))),
+            }
+          }
+
+          FileValue::Multi(entries)
+        }
+
+        (kind, value) => {
+          let expected = match kind {
+            Kind::Flag | Kind::NegatedFlag => "a boolean",
+            Kind::FlagOrValue => "a boolean or a string",
+            Kind::Value => "a string or an integer",
+            Kind::Multi => "an array of strings",
+            Kind::Paths => "a string or an array of strings",
+            Kind::Map => "a table",
+          };
+
+          return Err(
+            format!(
+              "{}: key '{file_key}' must be {expected}, found {}",
+              self.path.display(),
+              value.type_str()
+            )
+            .into(),
+          );
+        }
+      };
+
+      self.values.insert(name, (file_key, value));
+    }
+
+    Ok(())
+  }
+
+  fn read_strings(&self, key: &str, values: &[Value]) -> Result<Vec<String>, Box<dyn Error>> {
+    values
+      .iter()
+      .map(|value| match value {
+        Value::String(value) => Ok(value.clone()),
+        value => Err(format!("{}: key '{key}' must only contain strings, found {}", self.path.display(), value.type_str()).into()),
+      })
+      .collect()
+  }
+}
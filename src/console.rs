@@ -0,0 +1,143 @@
+use std::{
+  fs,
+  io::{self, Write},
+  os::fd::AsRawFd,
+  sync::atomic::{AtomicBool, Ordering},
+};
+
+use crossterm::{
+  event::{KeyCode, KeyEvent, KeyEventState, KeyModifiers, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags},
+  execute,
+  terminal::supports_keyboard_enhancement,
+};
+use nix::{ioctl_read_bad, libc::c_char};
+
+// Lock bits returned by the keyboard ioctls, from `linux/kd.h`.
+const LED_SCR: c_char = 0x01;
+const LED_NUM: c_char = 0x02;
+const LED_CAP: c_char = 0x04;
+
+// Reads the keyboard lock flags, which reflect the actual lock state even when
+// the LEDs have been set to something else.
+ioctl_read_bad!(kd_get_kb_led, 0x4B64, c_char);
+// Reads the keyboard LEDs, for older kernels without KDGKBLED.
+ioctl_read_bad!(kd_get_led, 0x4B31, c_char);
+
+// Whether the kitty keyboard protocol was enabled, and needs to be disabled
+// before leaving.
+static KEYBOARD_ENHANCED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LockState {
+  pub caps: bool,
+  pub num: bool,
+  pub scroll: bool,
+}
+
+// Where the state of the lock keys can be read from.
+#[derive(Clone, Copy, PartialEq)]
+pub enum LockSource {
+  // The Linux virtual console, through ioctls on the terminal.
+  Console,
+  // The modifier state reported with key events by terminal emulators
+  // implementing the kitty keyboard protocol.
+  Keyboard,
+  None,
+}
+
+impl LockSource {
+  // Finds out how the lock state can be read, enabling the kitty keyboard
+  // protocol if needed. This must be called after entering raw mode, and before
+  // listening for terminal events.
+  pub fn detect() -> LockSource {
+    if lock_state().is_some() {
+      return LockSource::Console;
+    }
+
+    if let Ok(true) = supports_keyboard_enhancement() {
+      // Lock modifiers are only reported for keys producing text when all keys
+      // are reported as escape codes. Alternate keys are needed for the shifted
+      // version of characters to still be received.
+      let flags = KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES
+        | KeyboardEnhancementFlags::REPORT_ALL_KEYS_AS_ESCAPE_CODES
+        | KeyboardEnhancementFlags::REPORT_ALTERNATE_KEYS;
+
+      if execute!(io::stdout(), PushKeyboardEnhancementFlags(flags)).is_ok() {
+        KEYBOARD_ENHANCED.store(true, Ordering::SeqCst);
+
+        return LockSource::Keyboard;
+      }
+    }
+
+    tracing::info!("lock state is not available on this terminal");
+
+    LockSource::None
+  }
+}
+
+// Whether tuigreet runs on a Linux virtual console, named `/dev/ttyN`, as
+// opposed to a pseudo-terminal or a serial line.
+pub fn is_virtual_console() -> bool {
+  let Ok(path) = fs::read_link("/proc/self/fd/0") else {
+    return false;
+  };
+
+  path
+    .to_str()
+    .and_then(|path| path.strip_prefix("/dev/tty"))
+    .is_some_and(|number| !number.is_empty() && number.bytes().all(|c| c.is_ascii_digit()))
+}
+
+// Reads the lock state of the virtual console tuigreet runs on. This fails
+// when the terminal is not a Linux virtual console.
+pub fn lock_state() -> Option<LockState> {
+  let fd = io::stdin().as_raw_fd();
+  let mut flags: c_char = 0;
+
+  unsafe {
+    if kd_get_kb_led(fd, &mut flags).is_err() {
+      kd_get_led(fd, &mut flags).ok()?;
+    }
+  }
+
+  Some(LockState {
+    caps: flags & LED_CAP != 0,
+    num: flags & LED_NUM != 0,
+    scroll: flags & LED_SCR != 0,
+  })
+}
+
+// Reads the lock state reported with a key event, when the kitty keyboard
+// protocol is enabled. Scroll Lock is not reported by the protocol.
+pub fn key_lock_state(key: &KeyEvent) -> LockState {
+  LockState {
+    caps: key.state.contains(KeyEventState::CAPS_LOCK),
+    num: key.state.contains(KeyEventState::NUM_LOCK),
+    scroll: false,
+  }
+}
+
+// With the kitty keyboard protocol, letters are reported without Caps Lock
+// being applied, so it is applied here.
+pub fn apply_caps_lock(mut key: KeyEvent) -> KeyEvent {
+  if let KeyCode::Char(c) = key.code {
+    if key.state.contains(KeyEventState::CAPS_LOCK) && c.is_alphabetic() && !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) {
+      let inverted = match c.is_uppercase() {
+        true => c.to_lowercase().next(),
+        false => c.to_uppercase().next(),
+      };
+
+      key.code = KeyCode::Char(inverted.unwrap_or(c));
+    }
+  }
+
+  key
+}
+
+// Disables the kitty keyboard protocol if it was enabled.
+pub fn restore() {
+  if KEYBOARD_ENHANCED.swap(false, Ordering::SeqCst) {
+    let _ = execute!(io::stdout(), PopKeyboardEnhancementFlags);
+    let _ = io::stdout().flush();
+  }
+}
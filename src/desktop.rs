@@ -0,0 +1,276 @@
+use std::path::Path;
+
+use ini::Properties;
+
+// Looks up a localized string, like `Name[fr_FR]`, trying the keys of the
+// locale from the most to the least specific before the unlocalized one.
+pub fn localized(section: &Properties, key: &str, locale: &str) -> Option<String> {
+  locale_keys(locale)
+    .iter()
+    .find_map(|locale| section.get(format!("{key}[{locale}]")))
+    .or_else(|| section.get(key))
+    .map(unescape)
+}
+
+// Keys a localized value is looked up with for a locale of the form
+// `lang_COUNTRY.ENCODING@MODIFIER`, as defined by the specification. The
+// encoding is ignored.
+fn locale_keys(locale: &str) -> Vec<String> {
+  let (locale, modifier) = match locale.split_once('@') {
+    Some((locale, modifier)) => (locale, Some(modifier)),
+    None => (locale, None),
+  };
+
+  let locale = locale.split('.').next().unwrap_or_default();
+
+  let (lang, country) = match locale.split_once('_') {
+    Some((lang, country)) => (lang, Some(country)),
+    None => (locale, None),
+  };
+
+  if lang.is_empty() {
+    return Vec::new();
+  }
+
+  let mut keys = Vec::new();
+
+  if let (Some(country), Some(modifier)) = (country, modifier) {
+    keys.push(format!("{lang}_{country}@{modifier}"));
+  }
+
+  if let Some(country) = country {
+    keys.push(format!("{lang}_{country}"));
+  }
+
+  if let Some(modifier) = modifier {
+    keys.push(format!("{lang}@{modifier}"));
+  }
+
+  keys.push(lang.to_string());
+  keys
+}
+
+// Replaces the escape sequences allowed in string values.
+pub fn unescape(value: &str) -> String {
+  let mut output = String::new();
+  let mut chars = value.chars();
+
+  while let Some(c) = chars.next() {
+    if c != '\\' {
+      output.push(c);
+      continue;
+    }
+
+    match chars.next() {
+      Some('s') => output.push(' '),
+      Some('n') => output.push('\n'),
+      Some('t') => output.push('\t'),
+      Some('r') => output.push('\r'),
+      Some(c) => output.push(c),
+      None => output.push('\\'),
+    }
+  }
+
+  output
+}
+
+// What the field codes of an `Exec` key are expanded to.
+pub struct Entry<'a> {
+  pub name: &'a str,
+  pub icon: Option<&'a str>,
+  pub path: &'a Path,
+}
+
+// Splits the already unescaped value of an `Exec` key into arguments, and
+// expands its field codes. Since sessions are not started with files or URLs,
+// the codes standing for them are removed.
+pub fn parse_exec(exec: &str, entry: &Entry) -> Result<Vec<String>, String> {
+  let mut args = Vec::new();
+
+  for (arg, quoted) in split_exec(exec)? {
+    if quoted {
+      args.push(arg);
+      continue;
+    }
+
+    match arg.as_str() {
+      "%f" | "%F" | "%u" | "%U" | "%d" | "%D" | "%n" | "%N" | "%v" | "%m" => {}
+
+      "%i" => {
+        if let Some(icon) = entry.icon {
+          args.push("--icon".to_string());
+          args.push(icon.to_string());
+        }
+      }
+
+      _ => args.push(expand_codes(&arg, entry)),
+    }
+  }
+
+  match args.is_empty() {
+    true => Err("empty Exec property in desktop file".to_string()),
+    false => Ok(args),
+  }
+}
+
+// Splits an `Exec` value on spaces, handling double-quoted arguments, within
+// which `"`, `` ` ``, `$` and `\` must be escaped with a backslash. Each
+// argument is returned along with whether it was quoted.
+fn split_exec(exec: &str) -> Result<Vec<(String, bool)>, String> {
+  let mut args = Vec::new();
+  let mut chars = exec.chars().peekable();
+
+  loop {
+    while chars.next_if(|c| c.is_ascii_whitespace()).is_some() {}
+
+    let Some(c) = chars.next() else {
+      break;
+    };
+
+    if c != '"' {
+      let mut arg = c.to_string();
+
+      while let Some(c) = chars.next_if(|c| !c.is_ascii_whitespace()) {
+        arg.push(c);
+      }
+
+      args.push((arg, false));
+      continue;
+    }
+
+    let mut arg = String::new();
+
+    loop {
+      match chars.next() {
+        Some('"') => break,
+        Some('\\') => match chars.next() {
+          Some(c @ ('"' | '`' | '$' | '\\')) => arg.push(c),
+          Some(c) => {
+            arg.push('\\');
+            arg.push(c);
+          }
+          None => return Err("unterminated quote in Exec property".to_string()),
+        },
+        Some(c) => arg.push(c),
+        None => return Err("unterminated quote in Exec property".to_string()),
+      }
+    }
+
+    args.push((arg, true));
+  }
+
+  Ok(args)
+}
+
+fn expand_codes(arg: &str, entry: &Entry) -> String {
+  let mut output = String::new();
+  let mut chars = arg.chars();
+
+  while let Some(c) = chars.next() {
+    if c != '%' {
+      output.push(c);
+      continue;
+    }
+
+    match chars.next() {
+      Some('%') => output.push('%'),
+      Some('c') => output.push_str(entry.name),
+      Some('k') => output.push_str(&entry.path.to_string_lossy()),
+      Some('i') => output.push_str(entry.icon.unwrap_or_default()),
+      _ => {}
+    }
+  }
+
+  output
+}
+
+// Joins arguments into a command line for the shell greetd starts sessions
+// with, quoting the ones that need it.
+pub fn shell_join(args: &[String]) -> String {
+  let is_safe = |c: char| c.is_ascii_alphanumeric() || "_@%+=:,./-".contains(c);
+
+  args
+    .iter()
+    .map(|arg| match !arg.is_empty() && arg.chars().all(is_safe) {
+      true => arg.clone(),
+      false => format!("'{}'", arg.replace('\'', r"'\''")),
+    })
+    .collect::<Vec<_>>()
+    .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::Path;
+
+  use ini::Properties;
+
+  use super::{locale_keys, localized, parse_exec, shell_join, unescape, Entry};
+
+  #[test]
+  fn locale_lookup_order() {
+    assert_eq!(locale_keys("sr_RS.UTF-8@latin"), ["sr_RS@latin", "sr_RS", "sr@latin", "sr"]);
+    assert_eq!(locale_keys("fr_FR"), ["fr_FR", "fr"]);
+    assert_eq!(locale_keys("de@euro"), ["de@euro", "de"]);
+    assert!(locale_keys("").is_empty());
+  }
+
+  #[test]
+  fn localized_values() {
+    let mut section = Properties::new();
+    section.insert("Name", "Plasma");
+    section.insert("Name[fr]", "Plasma\\sfrançais");
+    section.insert("Name[sr@latin]", "Plazma");
+
+    assert_eq!(localized(&section, "Name", "fr_CA").as_deref(), Some("Plasma français"));
+    assert_eq!(localized(&section, "Name", "sr_RS@latin").as_deref(), Some("Plazma"));
+    assert_eq!(localized(&section, "Name", "en_US").as_deref(), Some("Plasma"));
+    assert_eq!(localized(&section, "Comment", "fr_FR"), None);
+  }
+
+  #[test]
+  fn string_escapes() {
+    assert_eq!(unescape(r"a\sb\tc\nd\\e"), "a b\tc\nd\\e");
+    assert_eq!(unescape(r"trailing\"), "trailing\\");
+  }
+
+  #[test]
+  fn exec_arguments() {
+    let entry = Entry {
+      name: "Sway",
+      icon: Some("sway"),
+      path: Path::new("/usr/share/wayland-sessions/sway.desktop"),
+    };
+
+    let cases: &[(&str, &[&str])] = &[
+      ("sway", &["sway"]),
+      ("  sway  --unsupported-gpu ", &["sway", "--unsupported-gpu"]),
+      ("env XDG_CURRENT_DESKTOP=sway sway %U", &["env", "XDG_CURRENT_DESKTOP=sway", "sway"]),
+      (r#"sh -c "exec \"\$HOME/bin/sway\" \\ 'x'""#, &["sh", "-c", r#"exec "$HOME/bin/sway" \ 'x'"#]),
+      (r#"run "" end"#, &["run", "", "end"]),
+      ("sway %i --name=%c %k 100%%", &[
+        "sway",
+        "--icon",
+        "sway",
+        "--name=Sway",
+        "/usr/share/wayland-sessions/sway.desktop",
+        "100%",
+      ]),
+      ("sway %f %F %u %d %D %n %N %v %m", &["sway"]),
+    ];
+
+    for (exec, expected) in cases {
+      assert_eq!(parse_exec(exec, &entry).unwrap(), *expected, "{}", exec);
+    }
+
+    assert!(parse_exec(r#"sh -c "unterminated"#, &entry).is_err());
+    assert!(parse_exec("%f", &entry).is_err());
+  }
+
+  #[test]
+  fn shell_quoting() {
+    let args = ["sh", "-c", "exec \"$HOME/bin/sway\"", "it's", "", "--name=Sway"].map(str::to_string);
+
+    assert_eq!(shell_join(&args), r#"sh -c 'exec "$HOME/bin/sway"' 'it'\''s' '' --name=Sway"#);
+  }
+}
@@ -1,7 +1,5 @@
-use std::time::Duration;
-
-use crossterm::event::{Event as TermEvent, KeyEvent};
-use futures::{future::FutureExt, StreamExt};
+use crossterm::event::{Event as TermEvent, KeyCode, KeyEvent, MouseButton, MouseEvent, MouseEventKind};
+use futures::StreamExt;
 use tokio::{
   process::Command,
   sync::mpsc::{self, Sender},
@@ -9,14 +7,21 @@ use tokio::{
 
 use crossterm::event::EventStream;
 
-use crate::AuthStatus;
-
-const FRAME_RATE: f64 = 2.0;
+use crate::{
+  console::{self, LockSource},
+  helpers::Update,
+  AuthStatus,
+};
 
 pub enum Event {
   Key(KeyEvent),
+  Mouse(MouseEvent),
+  // Requests a redraw. Every event results in one, so this is only needed when
+  // the state changed outside of the main loop. Redraws are coalesced until no
+  // more events are pending.
   Render,
   PowerCommand(Command),
+  Helper(Update),
   Exit(AuthStatus),
 }
 
@@ -29,39 +34,78 @@ impl Events {
   pub async fn new() -> Events {
     let (tx, rx) = mpsc::channel(10);
 
+    Events { rx, tx }
+  }
+
+  // Starts listening for terminal events. This should only be called once the
+  // terminal is set up.
+  //
+  // When the lock state is read from key events, updates are sent whenever it
+  // changes.
+  pub fn listen(&self, locks: LockSource) {
     tokio::task::spawn({
-      let tx = tx.clone();
+      let tx = self.tx.clone();
 
       async move {
         let mut stream = EventStream::new();
+        let mut previous_locks = None;
+
+        while let Some(event) = stream.next().await {
+          match event {
+            // The layout is recomputed from the new size on the next render.
+            Ok(TermEvent::Resize(_, _)) => {
+              let _ = tx.send(Event::Render).await;
+            }
+
+            Ok(TermEvent::Key(mut event)) => {
+              if locks == LockSource::Keyboard {
+                let state = console::key_lock_state(&event);
 
-        let mut render_interval = tokio::time::interval(Duration::from_secs_f64(1.0 / FRAME_RATE));
+                if previous_locks != Some(state) {
+                  previous_locks = Some(state);
 
-        loop {
-          let render = render_interval.tick();
-          let event = stream.next().fuse();
+                  let _ = tx.send(Event::Helper(Update::Locks(state))).await;
+                }
 
-          tokio::select! {
-            event = event => {
-              if let Some(Ok(TermEvent::Key(event))) = event {
-                let _ = tx.send(Event::Key(event)).await;
-                let _ = tx.send(Event::Render).await;
+                event = console::apply_caps_lock(event);
+
+                // Modifier and lock keys are reported on their own with the
+                // kitty keyboard protocol, but are not meaningful to us.
+                if let KeyCode::Modifier(_) | KeyCode::CapsLock | KeyCode::NumLock | KeyCode::ScrollLock = event.code {
+                  continue;
+                }
               }
+
+              let _ = tx.send(Event::Key(event)).await;
+            }
+
+            // Only clicks and the wheel are used, so mouse motion does not cause
+            // redraws.
+            Ok(TermEvent::Mouse(
+              event @ MouseEvent {
+                kind: MouseEventKind::Down(MouseButton::Left) | MouseEventKind::ScrollUp | MouseEventKind::ScrollDown,
+                ..
+              },
+            )) => {
+              let _ = tx.send(Event::Mouse(event)).await;
             }
 
-            _ = render => { let _ = tx.send(Event::Render).await; },
+            _ => {}
           }
         }
       }
     });
-
-    Events { rx, tx }
   }
 
   pub async fn next(&mut self) -> Option<Event> {
     self.rx.recv().await
   }
 
+  // Whether events are waiting to be handled.
+  pub fn pending(&self) -> bool {
+    !self.rx.is_empty()
+  }
+
   pub fn sender(&self) -> Sender<Event> {
     self.tx.clone()
   }
@@ -1,17 +1,347 @@
-use std::process::Command;
+use std::{
+  error::Error,
+  fmt::{self, Display},
+  fs::{self, File},
+  io::{BufRead, BufReader, Read, Seek, SeekFrom},
+  path::{Path, PathBuf},
+  time::Duration,
+};
 
-pub fn get_fortune() -> String {
-  let output = Command::new("fortune").arg("-a").output();
-  match output {
-    Ok(result) if result.status.success() => String::from_utf8_lossy(&result.stdout).into_owned(),
-    _ => String::from("Sorry folks, no fortune at the moment!\n--- Fortune writer"),
+use rand::{seq::SliceRandom, Rng};
+
+use crate::helpers;
+
+const DEFAULT_FORTUNE: &str = "Sorry folks, no fortune at the moment!\n--- Fortune writer";
+const DEFAULT_DATABASES: &[&str] = &[
+  "/usr/share/fortune",
+  "/usr/share/fortunes",
+  "/usr/share/games/fortune",
+  "/usr/share/games/fortunes",
+];
+
+// Flag set in strfile headers when entries are ROT13-encoded.
+const STR_ROTATED: u32 = 0x4;
+
+// Where the text displayed under the prompt comes from.
+#[derive(Clone, Debug)]
+pub enum FortuneSource {
+  // A strfile-format fortune database, or a directory of such databases.
+  Strfile(PathBuf),
+  // The whole content of a file, like a message of the day.
+  File(PathBuf),
+  // A file picked at random from a directory of snippets.
+  Directory(PathBuf),
+  // The output of a command.
+  Command(String),
+}
+
+impl FortuneSource {
+  // Parses a source from its `TYPE:VALUE` representation.
+  pub fn parse(spec: &str) -> Result<FortuneSource, String> {
+    match spec.split_once(':') {
+      Some(("strfile", path)) => Ok(FortuneSource::Strfile(path.into())),
+      Some(("file", path)) => Ok(FortuneSource::File(path.into())),
+      Some(("dir", path)) => Ok(FortuneSource::Directory(path.into())),
+      Some(("command", command)) if command.trim().is_empty() => Err("the command must not be empty".to_string()),
+      Some(("command", command)) => Ok(FortuneSource::Command(command.to_string())),
+
+      _ => Err("must be one of strfile:PATH, file:PATH, dir:PATH or command:CMD".to_string()),
+    }
+  }
+
+  // Looks for a fortune database in the usual system locations.
+  pub fn system() -> Option<FortuneSource> {
+    DEFAULT_DATABASES
+      .iter()
+      .map(Path::new)
+      .find(|path| path.is_dir())
+      .map(|path| FortuneSource::Strfile(path.into()))
+  }
+}
+
+impl Display for FortuneSource {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      FortuneSource::Strfile(path) => write!(f, "strfile:{}", path.display()),
+      FortuneSource::File(path) => write!(f, "file:{}", path.display()),
+      FortuneSource::Directory(path) => write!(f, "dir:{}", path.display()),
+      FortuneSource::Command(command) => write!(f, "command:{command}"),
+    }
+  }
+}
+
+// Fetches a new fortune from the configured source, falling back to a default
+// message if it fails.
+pub async fn get_fortune(source: Option<&FortuneSource>, timeout: Duration) -> String {
+  let fortune = match source {
+    None => Err("no fortune source could be found".into()),
+    Some(FortuneSource::Strfile(path)) => read_strfile(path),
+    Some(FortuneSource::File(path)) => fs::read_to_string(path).map_err(Into::into),
+    Some(FortuneSource::Directory(path)) => read_snippet(path),
+    Some(FortuneSource::Command(command)) => helpers::run_command(command, timeout).await,
+  };
+
+  match fortune {
+    Ok(fortune) => fortune,
+
+    Err(err) => {
+      tracing::info!("could not get fortune: {err}");
+
+      DEFAULT_FORTUNE.to_string()
+    }
+  }
+}
+
+fn read_snippet(path: &Path) -> Result<String, Box<dyn Error>> {
+  let files: Vec<PathBuf> = fs::read_dir(path)?
+    .flatten()
+    .map(|entry| entry.path())
+    .filter(|path| path.is_file())
+    .collect();
+
+  let file = files.choose(&mut rand::thread_rng()).ok_or("snippet directory is empty")?;
+
+  Ok(fs::read_to_string(file)?)
+}
+
+// Picks a random entry from a strfile database.
+//
+// If the path is a directory, a database is first chosen at random among the
+// files it contains. The `.dat` index is used when present, otherwise the
+// database itself is read and split on its `%` delimiters.
+fn read_strfile(path: &Path) -> Result<String, Box<dyn Error>> {
+  let database = if path.is_dir() {
+    let databases: Vec<PathBuf> = fs::read_dir(path)?
+      .flatten()
+      .map(|entry| entry.path())
+      .filter(|path| path.is_file() && !matches!(path.extension(), Some(ext) if ext == "dat" || ext == "u8"))
+      .collect();
+
+    databases.choose(&mut rand::thread_rng()).cloned().ok_or("no fortune database found")?
+  } else {
+    path.to_path_buf()
+  };
+
+  let mut index = database.clone().into_os_string();
+  index.push(".dat");
+
+  match File::open(&index) {
+    Ok(index) => read_indexed_entry(&database, index),
+    Err(_) => read_unindexed_entry(&database),
+  }
+}
+
+// Header of a strfile `.dat` index, which is followed by the offset of each
+// entry in the database.
+struct Header {
+  count: u32,
+  flags: u32,
+  delimiter: char,
+}
+
+impl Header {
+  fn read(index: &mut impl Read) -> Result<Header, Box<dyn Error>> {
+    let mut header = [0u8; 24];
+    index.read_exact(&mut header)?;
+
+    let field = |n: usize| u32::from_be_bytes([header[n * 4], header[n * 4 + 1], header[n * 4 + 2], header[n * 4 + 3]]);
+
+    Ok(Header {
+      count: field(1),
+      flags: field(4),
+      delimiter: header[20] as char,
+    })
+  }
+}
+
+fn read_indexed_entry(database: &Path, mut index: File) -> Result<String, Box<dyn Error>> {
+  let header = Header::read(&mut index)?;
+
+  if header.count == 0 {
+    return Err("fortune database is empty".into());
+  }
+
+  let number = rand::thread_rng().gen_range(0..header.count);
+
+  read_entry(database, &mut index, &header, number)
+}
+
+// Reads an entry of a database from its number, using an index positioned
+// right after its header.
+fn read_entry<I>(database: &Path, index: &mut I, header: &Header, number: u32) -> Result<String, Box<dyn Error>>
+where
+  I: Read + Seek,
+{
+  let mut offset = [0u8; 4];
+  index.seek(SeekFrom::Current(4 * number as i64))?;
+  index.read_exact(&mut offset)?;
+
+  let mut reader = BufReader::new(File::open(database)?);
+  reader.seek(SeekFrom::Start(u32::from_be_bytes(offset) as u64))?;
+
+  let mut entry = String::new();
+
+  for line in reader.lines() {
+    let line = line?;
+
+    if line.len() == 1 && line.starts_with(header.delimiter) {
+      break;
+    }
+
+    entry.push_str(&line);
+    entry.push('\n');
   }
+
+  match header.flags & STR_ROTATED {
+    0 => Ok(entry),
+    _ => Ok(rot13(&entry)),
+  }
+}
+
+fn read_unindexed_entry(database: &Path) -> Result<String, Box<dyn Error>> {
+  let entries = split_entries(&fs::read_to_string(database)?);
+
+  entries.choose(&mut rand::thread_rng()).cloned().ok_or_else(|| "fortune database is empty".into())
 }
 
-pub fn get_figlet(str: &String) -> String {
-  let output = Command::new("figlet").arg("-f").arg("sblood").arg(str).output();
-  match output {
-    Ok(result) if result.status.success() => String::from_utf8_lossy(&result.stdout).into_owned(),
-    _ => str.to_owned(),
+// Splits the content of a database on its `%` delimiters, leaving out blank
+// entries.
+fn split_entries(content: &str) -> Vec<String> {
+  let mut entries = vec![String::new()];
+
+  for line in content.lines() {
+    match line {
+      "%" => entries.push(String::new()),
+
+      line => {
+        if let Some(entry) = entries.last_mut() {
+          entry.push_str(line);
+          entry.push('\n');
+        }
+      }
+    }
+  }
+
+  entries.retain(|entry| !entry.trim().is_empty());
+  entries
+}
+
+fn rot13(text: &str) -> String {
+  text
+    .chars()
+    .map(|c| match c {
+      'a'..='z' => (((c as u8 - b'a' + 13) % 26) + b'a') as char,
+      'A'..='Z' => (((c as u8 - b'A' + 13) % 26) + b'A') as char,
+      c => c,
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    fs::{self, File},
+    io::{Seek, SeekFrom},
+    path::Path,
+  };
+
+  use tempfile::TempDir;
+
+  use super::{read_entry, read_strfile, rot13, split_entries, FortuneSource, Header, STR_ROTATED};
+
+  const DATABASE: &str = "First entry\n%\nSecond\nentry\n%\nThird entry\n%\n";
+
+  // Writes a database along with its index, as `strfile` would.
+  fn database(dir: &Path, name: &str, content: &str, flags: u32) {
+    let mut offsets = vec![0u32];
+
+    for (index, _) in content.match_indices("%\n") {
+      offsets.push((index + 2) as u32);
+    }
+
+    let mut index = Vec::new();
+
+    for field in [2, offsets.len() as u32 - 1, 0, 0, flags] {
+      index.extend(field.to_be_bytes());
+    }
+
+    index.extend([b'%', 0, 0, 0]);
+
+    for offset in offsets {
+      index.extend(offset.to_be_bytes());
+    }
+
+    fs::write(dir.join(name), content).unwrap();
+    fs::write(dir.join(format!("{name}.dat")), index).unwrap();
+  }
+
+  #[test]
+  fn sources() {
+    assert!(matches!(
+      FortuneSource::parse("command:fortune -s"),
+      Ok(FortuneSource::Command(command)) if command == "fortune -s"
+    ));
+    assert!(matches!(FortuneSource::parse("file:/etc/motd"), Ok(FortuneSource::File(_))));
+    assert!(FortuneSource::parse("command:").is_err());
+    assert!(FortuneSource::parse("command:  ").is_err());
+    assert!(FortuneSource::parse("/usr/share/fortune").is_err());
+    assert!(FortuneSource::parse("url:https://example.com").is_err());
+    assert_eq!(FortuneSource::parse("dir:/etc/motd.d").unwrap().to_string(), "dir:/etc/motd.d");
+  }
+
+  #[test]
+  fn indexed_databases() {
+    let dir = TempDir::new().unwrap();
+    database(dir.path(), "plain", DATABASE, 0);
+
+    let mut index = File::open(dir.path().join("plain.dat")).unwrap();
+    let header = Header::read(&mut index).unwrap();
+
+    assert_eq!(header.count, 3);
+    assert_eq!(header.flags, 0);
+    assert_eq!(header.delimiter, '%');
+
+    let database = dir.path().join("plain");
+    let expected = ["First entry\n", "Second\nentry\n", "Third entry\n"];
+
+    for (number, expected) in expected.iter().enumerate() {
+      index.seek(SeekFrom::Start(24)).unwrap();
+
+      assert_eq!(read_entry(&database, &mut index, &header, number as u32).unwrap(), *expected);
+    }
+
+    assert!(expected.contains(&read_strfile(&database).unwrap().as_str()));
+  }
+
+  #[test]
+  fn rotated_databases() {
+    let dir = TempDir::new().unwrap();
+    database(dir.path(), "rotated", &rot13("Hello, World!\n%\n"), STR_ROTATED);
+
+    assert_eq!(rot13("Hello, World!"), "Uryyb, Jbeyq!");
+    assert_eq!(read_strfile(&dir.path().join("rotated")).unwrap(), "Hello, World!\n");
+  }
+
+  #[test]
+  fn empty_databases() {
+    let dir = TempDir::new().unwrap();
+    database(dir.path(), "empty", "", 0);
+
+    assert!(read_strfile(&dir.path().join("empty")).is_err());
+  }
+
+  #[test]
+  fn unindexed_databases() {
+    assert_eq!(split_entries(DATABASE), ["First entry\n", "Second\nentry\n", "Third entry\n"]);
+    assert_eq!(split_entries("%\nOnly\n\n%\n   \n%\n"), ["Only\n\n"]);
+    assert_eq!(split_entries("100%\nNo delimiter"), ["100%\nNo delimiter\n"]);
+    assert!(split_entries("").is_empty());
+
+    // Databases without an index, or directories of them, are split as well.
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("only"), "%\nSingle entry\n%\n").unwrap();
+
+    assert_eq!(read_strfile(&dir.path().join("only")).unwrap(), "Single entry\n");
+    assert_eq!(read_strfile(dir.path()).unwrap(), "Single entry\n");
   }
 }
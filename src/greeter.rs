@@ -4,42 +4,51 @@ use std::{
   error::Error,
   ffi::OsStr,
   fmt::{self, Display},
-  path::PathBuf,
   process,
   sync::Arc,
 };
 
 use chrono::Locale;
-use getopts::{Matches, Options};
+use getopts::Options;
 use i18n_embed::DesktopLanguageRequester;
 use tokio::{
   net::UnixStream,
   sync::{mpsc::Sender, RwLock, RwLockWriteGuard},
 };
 use tracing_appender::non_blocking::WorkerGuard;
+use unicode_width::UnicodeWidthStr;
 use zeroize::Zeroize;
 
 use crate::{
+  completion::Completion,
+  config::{Config, DEFAULT_CONFIG_FILE},
+  console::LockState,
   event::Event,
+  helpers::Update,
+  history::History,
+  layout::get_console_layout,
   info::{
-    get_issue, get_last_command, get_last_session_path, get_last_user_command, get_last_user_name,
-    get_last_user_session, get_last_user_username, get_min_max_uids, get_sessions, get_users,
+    get_hostname, get_last_command, get_last_session_path, get_last_user_command, get_last_user_name,
+    get_last_user_session, get_last_user_username, get_sessions, get_users,
   },
+  issue::Issue,
   power::PowerOption,
+  settings::{Settings, DEFAULT_XSESSION_WRAPPER},
   ui::{
-    common::{masked::MaskedString, menu::Menu, style::Theme},
+    clock::Clock,
+    screen::Regions,
+    common::{
+      editor::LineEditor,
+      masked::MaskedString,
+      menu::{Menu, MenuControl},
+    },
     power::Power,
-    sessions::{Session, SessionSource, SessionType},
+    sessions::{Session, SessionSource},
     users::User,
   },
 };
 
-const DEFAULT_LOG_FILE: &str = "/tmp/tuigreet.log";
 const DEFAULT_LOCALE: Locale = Locale::en_US;
-const DEFAULT_ASTERISKS_CHARS: &str = "*";
-// `startx` wants an absolute path to the executable as a first argument.
-// We don't want to resolve the session command in the greeter though, so it should be additionally wrapped with a known noop command (like `/usr/bin/env`).
-const DEFAULT_XSESSION_WRAPPER: &str = "startx /usr/bin/env";
 
 #[derive(Debug, Copy, Clone)]
 pub enum AuthStatus {
@@ -101,13 +110,12 @@ pub enum GreetAlign {
 
 #[derive(SmartDefault)]
 pub struct Greeter {
-  pub debug: bool,
-  pub logfile: String,
   pub logger: Option<WorkerGuard>,
 
   #[default(DEFAULT_LOCALE)]
   pub locale: Locale,
-  pub config: Option<Matches>,
+  // Settings resolved from the command line and the configuration file.
+  pub settings: Settings,
   pub socket: String,
   pub stream: Option<Arc<RwLock<UnixStream>>>,
   pub events: Option<Sender<Event>>,
@@ -116,28 +124,21 @@ pub struct Greeter {
   pub mode: Mode,
   // Mode the application will return to when exiting the current mode.
   pub previous_mode: Mode,
-  // Offset the cursor should be at from its base position for the current mode.
-  pub cursor_offset: i16,
 
-  // Buffer to be used as a temporary editing zone for the various modes.
-  // Previous buffer is saved when a transient screen has to use the buffer, to
-  // be able to restore it when leaving the transient screen.
-  pub previous_buffer: Option<String>,
-  pub buffer: String,
+  // Answer to the current prompt from greetd, like a password.
+  pub answer: LineEditor,
+  // Command being edited in the command prompt.
+  pub command: LineEditor,
+  // Commands previously used by the current user.
+  pub history: History,
+  // Candidates for the command being edited.
+  pub completion: Completion,
 
   // Define the selected session and how to resolve it.
   pub session_source: SessionSource,
-  // List of session files found on disk.
-  pub session_paths: Vec<(PathBuf, SessionType)>,
   // Menu for session selection.
   pub sessions: Menu<Session>,
-  // Wrapper command to prepend to non-X11 sessions.
-  pub session_wrapper: Option<String>,
-  // Wrapper command to prepend to X11 sessions.
-  pub xsession_wrapper: Option<String>,
 
-  // Whether user menu is enabled.
-  pub user_menu: bool,
   // Menu for user selection.
   pub users: Menu<User>,
   // Current username. Masked to display the full name if available.
@@ -147,38 +148,29 @@ pub struct Greeter {
 
   // Whether the current edition prompt should be hidden.
   pub asking_for_secret: bool,
-  // How should secrets be displayed?
-  pub secret_display: SecretDisplay,
-
-  // Whether last logged-in user should be remembered.
-  pub remember: bool,
-  // Whether last launched session (regardless of user) should be remembered.
-  pub remember_session: bool,
-  // Whether last launched session for the current user should be remembered.
-  pub remember_user_session: bool,
-
-  // Style object for the terminal UI
-  pub theme: Theme,
-  // Greeting message (MOTD) to use to welcome the user.
-  pub greeting: Option<String>,
+
+  // Issue file and the host information it refers to, with `--issue`. It is
+  // collected again periodically, and rendered when drawn.
+  pub issue: Option<Issue>,
+  // Hostname, as last read.
+  pub hostname: String,
   // Transaction message to show to the user.
   pub message: Option<String>,
 
   // Menu for power options.
   pub powers: Menu<Power>,
-  // Whether to prefix the power commands with `setsid`.
-  pub power_setsid: bool,
-
-  #[default(2)]
-  pub kb_command: u8,
-  #[default(3)]
-  pub kb_sessions: u8,
-  #[default(6)]
-  pub kb_fortune: u8,
-  #[default(12)]
-  pub kb_power: u8,
 
   pub fortune: String,
+  // Output of the command widget.
+  pub widget_output: String,
+  // State of the lock keys, as last reported by the console or the terminal.
+  pub locks: LockState,
+  // Keymap currently loaded into the console, if known.
+  pub layout: Option<String>,
+  // Large clock displayed above the prompt.
+  pub clock: Clock,
+  // Parts of the screen that respond to mouse clicks, as last drawn.
+  pub regions: Regions,
 
   pub clear_request: bool,
 
@@ -203,21 +195,9 @@ impl Greeter {
     greeter.events = Some(events);
     greeter.set_locale();
 
-    greeter.powers = Menu {
-      title: fl!("title_power"),
-      options: Default::default(),
-      selected: 0,
-    };
+    greeter.powers = Menu::new(fl!("title_power"), Default::default());
 
     {
-      match env::var("GREETD_SOCK") {
-        Ok(socket) => greeter.socket = socket,
-        Err(_) => {
-          eprintln!("GREETD_SOCK must be defined");
-          process::exit(1);
-        }
-      }
-
       let args = env::args().collect::<Vec<String>>();
 
       if let Err(err) = greeter.parse_options(&args).await {
@@ -227,6 +207,18 @@ impl Greeter {
         process::exit(1);
       }
 
+      if greeter.settings.check_config {
+        process::exit(crate::check::run(&greeter));
+      }
+
+      match env::var("GREETD_SOCK") {
+        Ok(socket) => greeter.socket = socket,
+        Err(_) => {
+          eprintln!("GREETD_SOCK must be defined");
+          process::exit(1);
+        }
+      }
+
       greeter.connect().await;
     }
 
@@ -240,19 +232,15 @@ impl Greeter {
       }
     }
 
-    greeter.sessions = Menu {
-      title: fl!("title_session"),
-      options: sessions,
-      selected: 0,
-    };
+    greeter.sessions = Menu::new(fl!("title_session"), sessions);
 
     // If we should remember the last logged-in user.
-    if greeter.remember {
+    if greeter.settings.remember {
       if let Some(username) = get_last_user_username() {
         greeter.username = MaskedString::from(username, get_last_user_name());
 
         // If, on top of that, we should remember their last session.
-        if greeter.remember_user_session {
+        if greeter.settings.remember_user_session {
           // See if we have the last free-form command from the user.
           if let Ok(command) = get_last_user_command(greeter.username.get()) {
             greeter.session_source = SessionSource::Command(command);
@@ -276,7 +264,7 @@ impl Greeter {
     }
 
     // Same thing, but not user specific.
-    if greeter.remember_session {
+    if greeter.settings.remember_session {
       if let Ok(command) = get_last_command() {
         greeter.session_source = SessionSource::Command(command.trim().to_string());
       }
@@ -301,7 +289,8 @@ impl Greeter {
   // keep the username (can happen if a wrong password was entered, we want to
   // give the user another chance, as PAM would).
   fn scrub(&mut self, scrub_message: bool, soft: bool) {
-    self.buffer.zeroize();
+    self.answer.zeroize();
+    self.command.zeroize();
     self.prompt.zeroize();
 
     if !soft {
@@ -342,71 +331,10 @@ impl Greeter {
     }
   }
 
-  pub fn config(&self) -> &Matches {
-    self.config.as_ref().unwrap()
-  }
-
   pub async fn stream(&self) -> RwLockWriteGuard<'_, UnixStream> {
     self.stream.as_ref().unwrap().write().await
   }
 
-  pub fn option(&self, name: &str) -> Option<String> {
-    self.config().opt_str(name)
-  }
-
-  pub fn options_multi(&self, name: &str) -> Option<Vec<String>> {
-    match self.config().opt_present(name) {
-      true => Some(self.config().opt_strs(name)),
-      false => None,
-    }
-  }
-
-  // Returns the width of the main window where content is displayed from the
-  // provided arguments.
-  pub fn width(&self) -> u16 {
-    if let Some(value) = self.option("width") {
-      if let Ok(width) = value.parse::<u16>() {
-        return width;
-      }
-    }
-
-    80
-  }
-
-  // Returns the padding of the screen from the provided arguments.
-  pub fn window_padding(&self) -> u16 {
-    if let Some(value) = self.option("window-padding") {
-      if let Ok(padding) = value.parse::<u16>() {
-        return padding;
-      }
-    }
-
-    0
-  }
-
-  // Returns the padding of the main window where content is displayed from the
-  // provided arguments.
-  pub fn container_padding(&self) -> u16 {
-    if let Some(value) = self.option("container-padding") {
-      if let Ok(padding) = value.parse::<u16>() {
-        return padding + 1;
-      }
-    }
-
-    2
-  }
-
-  // Returns the spacing between each prompt from the provided arguments.
-  pub fn prompt_padding(&self) -> u16 {
-    if let Some(value) = self.option("prompt-padding") {
-      if let Ok(padding) = value.parse::<u16>() {
-        return padding;
-      }
-    }
-
-    1
-  }
-
   // Sets the locale that will be used for this invocation from environment.
   fn set_locale(&mut self) {
     let locale = DesktopLanguageRequester::requested_languages()
@@ -424,6 +352,30 @@ impl Greeter {
     if mode != self.mode {
       self.mode = mode;
       self.clear_request = true;
+
+      self.users.clear_filter();
+      self.sessions.clear_filter();
+      self.powers.clear_filter();
+    }
+  }
+
+  // Returns the line editor of the prompt displayed in the current mode, if any.
+  pub fn editor(&mut self) -> Option<&mut LineEditor> {
+    match self.mode {
+      Mode::Username => Some(&mut self.username.value),
+      Mode::Password => Some(&mut self.answer),
+      Mode::Command => Some(&mut self.command),
+      _ => None,
+    }
+  }
+
+  // Returns the menu displayed in the current mode, if any.
+  pub fn menu(&mut self) -> Option<&mut dyn MenuControl> {
+    match self.mode {
+      Mode::Users => Some(&mut self.users),
+      Mode::Sessions => Some(&mut self.sessions),
+      Mode::Power => Some(&mut self.powers),
+      _ => None,
     }
   }
 
@@ -435,6 +387,17 @@ impl Greeter {
 
     opts.optflag("h", "help", "show this usage information");
     opts.optflag("v", "version", "print version information");
+    opts.optopt(
+      "",
+      "config",
+      &format!("path to a TOML configuration file (default: {DEFAULT_CONFIG_FILE})"),
+      "FILE",
+    );
+    opts.optflag(
+      "",
+      "check-config",
+      "validate the configuration and the environment, then exit",
+    );
     opts.optflagopt(
       "d",
       "debug",
@@ -465,6 +428,52 @@ impl Greeter {
     opts.optflag("", "no-xsession-wrapper", "do not wrap commands for X11 sessions");
     opts.optopt("w", "width", "width of the main prompt (default: 80)", "WIDTH");
     opts.optflag("i", "issue", "show the host's issue file");
+    opts.optopt("g", "greeting", "show custom text above the prompt", "GREETING");
+    opts.optflag("t", "time", "display the current date and time");
+    opts.optopt(
+      "",
+      "clock-font",
+      "font used to draw the clock: block, lines, segment or plain (default: block)",
+      "FONT",
+    );
+    opts.optopt(
+      "",
+      "clock-format",
+      "custom strftime format for the clock, overrides --clock-12h and --clock-seconds",
+      "FORMAT",
+    );
+    opts.optflag("", "clock-12h", "display the clock in 12-hour format");
+    opts.optflag("", "clock-seconds", "display seconds in the clock");
+    opts.optopt(
+      "",
+      "date-format",
+      "custom strftime format for the date (default: from the locale)",
+      "FORMAT",
+    );
+    opts.optopt(
+      "",
+      "fortune",
+      "source of the text displayed under the prompt (default: the system fortune database)",
+      "strfile:PATH|file:PATH|dir:PATH|command:CMD",
+    );
+    opts.optopt(
+      "",
+      "fortune-timeout",
+      "maximum time a fortune command may run, in milliseconds (default: 1000)",
+      "MILLIS",
+    );
+    opts.optmulti(
+      "",
+      "layout",
+      "widgets displayed in a region of the screen, or none (can appear more than once)",
+      "REGION=WIDGET[:ALIGN][,WIDGET[:ALIGN]...]",
+    );
+    opts.optopt(
+      "",
+      "widget-command",
+      "command whose output is displayed by the command widget",
+      "'CMD [ARGS]...'",
+    );
     opts.optflag("r", "remember", "remember last logged-in username");
     opts.optflag("", "remember-session", "remember last selected session");
     opts.optflag(
@@ -485,7 +494,25 @@ impl Greeter {
       "maximum UID to display in the user selection menu",
       "UID",
     );
-    opts.optopt("", "theme", "define the application theme colors", "THEME");
+    opts.optopt(
+      "",
+      "keyboard-layouts",
+      "comma-separated list of console keymaps to cycle through",
+      "LAYOUTS",
+    );
+    opts.optflag("", "mouse", "select menu entries and status bar buttons with the mouse");
+    opts.optopt(
+      "",
+      "theme",
+      "name of the application theme, or inline theme specification",
+      "THEME",
+    );
+    opts.optopt(
+      "",
+      "color-depth",
+      "colors supported by the terminal (default: auto)",
+      "auto|monochrome|16|256|truecolor",
+    );
     opts.optflag("", "asterisks", "display asterisks when a secret is typed");
     opts.optopt(
       "",
@@ -526,9 +553,16 @@ impl Greeter {
     );
     opts.optflag("", "power-no-setsid", "do not prefix power commands with setsid");
 
+    opts.optmulti(
+      "",
+      "keybinding",
+      "bind an action to one or more key chords, or to none (can appear more than once)",
+      "ACTION=CHORD[,CHORD...]",
+    );
     opts.optopt("", "kb-command", "F-key to use to open the command menu", "[1-12]");
     opts.optopt("", "kb-sessions", "F-key to use to open the sessions menu", "[1-12]");
     opts.optopt("", "kb-fortune", "F-key to use to change fortune", "[1-12]");
+    opts.optopt("", "kb-layout", "F-key to use to switch keyboard layout", "[1-12]");
     opts.optopt("", "kb-power", "F-key to use to open the power menu", "[1-12]");
 
     opts
@@ -541,171 +575,90 @@ impl Greeter {
   {
     let opts = Greeter::options();
 
-    self.config = match opts.parse(args) {
-      Ok(matches) => Some(matches),
+    let matches = match opts.parse(args) {
+      Ok(matches) => matches,
       Err(err) => return Err(err.into()),
     };
 
-    if self.config().opt_present("help") {
+    if matches.opt_present("help") {
       print_usage(opts);
       process::exit(0);
     }
-    if self.config().opt_present("version") {
+    if matches.opt_present("version") {
       print_version();
       process::exit(0);
     }
 
-    if self.config().opt_present("debug") {
-      self.debug = true;
+    self.settings = Settings::from_config(&Config::new(matches)?)?;
 
-      self.logfile = match self.config().opt_str("debug") {
-        Some(file) => file.to_string(),
-        None => DEFAULT_LOG_FILE.to_string(),
-      }
-    }
-
-    if self.config().opt_present("theme") {
-      if let Some(spec) = self.config().opt_str("theme") {
-        self.theme = Theme::parse(spec.as_str());
-      }
-    }
-
-    if self.config().opt_present("asterisks") {
-      let asterisk = if let Some(value) = self.config().opt_str("asterisks-char") {
-        if value.chars().count() < 1 {
-          return Err("--asterisks-char must have at least one character as its value".into());
-        }
-
-        value
-      } else {
-        DEFAULT_ASTERISKS_CHARS.to_string()
-      };
-
-      self.secret_display = SecretDisplay::Character(asterisk);
-    }
-
-    if self.config().opt_present("user-menu") {
-      self.user_menu = true;
-
-      let min_uid = self
-        .config()
-        .opt_str("user-menu-min-uid")
-        .and_then(|uid| uid.parse::<u16>().ok());
-      let max_uid = self
-        .config()
-        .opt_str("user-menu-max-uid")
-        .and_then(|uid| uid.parse::<u16>().ok());
-      let (min_uid, max_uid) = get_min_max_uids(min_uid, max_uid);
+    if self.settings.user_menu {
+      let (min_uid, max_uid) = self.settings.user_menu_uids;
 
       tracing::info!("min/max UIDs are {}/{}", min_uid, max_uid);
 
-      if min_uid >= max_uid {
-        return Err("Minimum UID ({min_uid}) must be less than maximum UID ({max_uid})".into());
-      }
-
-      self.users = Menu {
-        title: fl!("title_users"),
-        options: get_users(min_uid, max_uid),
-        selected: 0,
-      };
+      self.users = Menu::new(fl!("title_users"), get_users(min_uid, max_uid));
 
       tracing::info!("found {} users", self.users.options.len());
     }
 
-    if self.config().opt_present("remember-session") && self.config().opt_present("remember-user-session") {
-      return Err("Only one of --remember-session and --remember-user-session may be used at the same time".into());
-    }
-    if self.config().opt_present("remember-user-session") && !self.config().opt_present("remember") {
-      return Err("--remember-session must be used with --remember".into());
-    }
-
-    self.remember = self.config().opt_present("remember");
-    self.remember_session = self.config().opt_present("remember-session");
-    self.remember_user_session = self.config().opt_present("remember-user-session");
-
     // If the `--cmd` argument is provided, it will override the selected session.
-    if let Some(command) = self.option("cmd") {
-      let envs = self.options_multi("env");
-
-      if let Some(envs) = envs {
-        for env in envs {
-          if !env.contains('=') {
-            return Err(format!("malformed environment variable definition for '{env}'").into());
-          }
-        }
-      }
-
-      self.session_source = SessionSource::DefaultCommand(command, self.options_multi("env"));
-    }
-
-    if let Some(dirs) = self.option("sessions") {
-      self
-        .session_paths
-        .extend(env::split_paths(&dirs).map(|dir| (dir, SessionType::Wayland)));
-    }
-
-    if let Some(dirs) = self.option("xsessions") {
-      self
-        .session_paths
-        .extend(env::split_paths(&dirs).map(|dir| (dir, SessionType::X11)));
+    if let Some((command, envs)) = &self.settings.command {
+      self.session_source = SessionSource::DefaultCommand(command.clone(), envs.clone());
     }
 
-    if self.option("session-wrapper").is_some() {
-      self.session_wrapper = self.option("session-wrapper");
+    // Lookups through the resolver are left to the background refresh, so
+    // that the greeter does not hang on an offline boot.
+    if self.settings.issue {
+      self.issue = Issue::collect(false);
     }
 
-    if !self.config().opt_present("no-xsession-wrapper") {
-      self.xsession_wrapper = self
-        .option("xsession-wrapper")
-        .or_else(|| Some(DEFAULT_XSESSION_WRAPPER.to_string()));
-    }
+    self.hostname = get_hostname();
 
-    if self.config().opt_present("issue") {
-      self.greeting = get_issue();
-    }
+    self.layout = get_console_layout();
 
     self.powers.options.push(Power {
       action: PowerOption::Shutdown,
       label: fl!("shutdown"),
-      command: self.config().opt_str("power-shutdown"),
+      command: self.settings.power_shutdown.clone(),
     });
 
     self.powers.options.push(Power {
       action: PowerOption::Reboot,
       label: fl!("reboot"),
-      command: self.config().opt_str("power-reboot"),
+      command: self.settings.power_reboot.clone(),
     });
 
-    self.power_setsid = !self.config().opt_present("power-no-setsid");
-
-    self.kb_command = self
-      .config()
-      .opt_str("kb-command")
-      .map(|i| i.parse::<u8>().unwrap_or_default())
-      .unwrap_or(2);
-    self.kb_sessions = self
-      .config()
-      .opt_str("kb-sessions")
-      .map(|i| i.parse::<u8>().unwrap_or_default())
-      .unwrap_or(3);
-    self.kb_fortune = self
-      .config()
-      .opt_str("kb-fortune")
-      .map(|i| i.parse::<u8>().unwrap_or_default())
-      .unwrap_or(6);
-    self.kb_power = self
-      .config()
-      .opt_str("kb-power")
-      .map(|i| i.parse::<u8>().unwrap_or_default())
-      .unwrap_or(12);
-
-    if self.kb_command == self.kb_sessions || self.kb_sessions == self.kb_power || self.kb_power == self.kb_command {
-      return Err("keybindings must all be distinct".into());
-    }
-
     Ok(())
   }
 
+  // Caches the result of a background helper.
+  pub fn update(&mut self, update: Update) {
+    match update {
+      Update::Fortune(fortune) => {
+        self.fortune = fortune;
+        self.clear_request = true;
+      }
+
+      Update::WidgetOutput(output) => self.widget_output = output,
+
+      Update::Locks(locks) => self.locks = locks,
+      Update::Issue(issue) => self.issue = *issue,
+      Update::Hostname(hostname) => self.hostname = hostname,
+
+      Update::Layout(layout, Ok(())) => {
+        tracing::info!("switched keyboard layout to {layout}");
+
+        self.layout = Some(layout);
+      }
+
+      Update::Layout(layout, Err(err)) => {
+        tracing::error!("could not switch keyboard layout to {layout}: {err}");
+
+        self.message = Some(fl!("layout_failed"));
+      }
+    }
+  }
+
   pub fn set_prompt(&mut self, prompt: &str) {
     self.prompt = if prompt.ends_with(' ') {
       Some(prompt.into())
@@ -722,7 +675,7 @@ impl Greeter {
   pub fn prompt_width(&self) -> usize {
     match &self.prompt {
       None => 0,
-      Some(prompt) => prompt.chars().count(),
+      Some(prompt) => prompt.width(),
     }
   }
 }
@@ -0,0 +1,245 @@
+use std::{
+  error::Error,
+  process::{Output, Stdio},
+  sync::Arc,
+  time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use tokio::{
+  process::Command,
+  sync::{mpsc::Sender, RwLock},
+};
+
+use crate::{
+  console::{self, LockState},
+  event::Event,
+  fortune::get_fortune,
+  info::get_hostname,
+  issue::Issue,
+  layout,
+  ui::clock,
+  Greeter,
+};
+
+// Interval at which the lock state of the console is refreshed.
+const LOCKS_INTERVAL: Duration = Duration::from_millis(500);
+// Interval at which the issue file and the information it refers to, like
+// network addresses and the number of logged in users, are collected again.
+const ISSUE_INTERVAL: Duration = Duration::from_secs(10);
+// Interval at which the hostname is read again.
+const HOSTNAME_INTERVAL: Duration = Duration::from_secs(30);
+// Maximum time the command of the command widget may run.
+const WIDGET_COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Result of a background helper, to be cached into the `Greeter`.
+pub enum Update {
+  Fortune(String),
+  // Output of the command widget.
+  WidgetOutput(String),
+  Locks(LockState),
+  Issue(Box<Option<Issue>>),
+  Hostname(String),
+  // Keyboard layout that was loaded, or the reason it could not be.
+  Layout(String, Result<(), String>),
+}
+
+// Runs a command, killing it if it does not complete within the provided
+// timeout.
+pub async fn output(mut command: Command, timeout: Duration) -> Result<Output, Box<dyn Error>> {
+  command.stdin(Stdio::null());
+  command.kill_on_drop(true);
+
+  match tokio::time::timeout(timeout, command.output()).await {
+    Ok(output) => Ok(output?),
+    Err(_) => Err(format!("command timed out after {}ms", timeout.as_millis()).into()),
+  }
+}
+
+// Runs a command, split into words like a shell would, without expanding
+// anything, and returns its standard output.
+pub async fn run_command(command: &str, timeout: Duration) -> Result<String, Box<dyn Error>> {
+  let args = shell_words::split(command).map_err(|err| format!("could not parse command '{command}': {err}"))?;
+
+  let Some((program, args)) = args.split_first() else {
+    return Err("command is empty".into());
+  };
+
+  let mut command = Command::new(program);
+  command.args(args);
+
+  match output(command, timeout).await? {
+    output if !output.status.success() => Err(format!("command exited with {}", output.status).into()),
+    output => Ok(String::from_utf8_lossy(&output.stdout).into_owned()),
+  }
+}
+
+// Fetches a new fortune in the background.
+pub fn refresh_fortune(greeter: &Greeter) {
+  let Some(sender) = greeter.events.clone() else {
+    return;
+  };
+
+  let source = greeter.settings.fortune.clone();
+  let timeout = greeter.settings.fortune_timeout;
+
+  tokio::task::spawn(async move {
+    let fortune = get_fortune(source.as_ref(), timeout).await;
+
+    let _ = sender.send(Event::Helper(Update::Fortune(fortune))).await;
+  });
+}
+
+// Runs the command of the command widget in the background.
+pub fn refresh_widget_command(greeter: &Greeter) {
+  let (Some(sender), Some(command)) = (greeter.events.clone(), greeter.settings.widget_command.clone()) else {
+    return;
+  };
+
+  tokio::task::spawn(async move {
+    let output = match run_command(&command, WIDGET_COMMAND_TIMEOUT).await {
+      Ok(output) => output,
+
+      Err(err) => {
+        tracing::error!("could not run widget command: {err}");
+
+        return;
+      }
+    };
+
+    let _ = sender.send(Event::Helper(Update::WidgetOutput(output))).await;
+  });
+}
+
+// Switches the console keyboard layout in the background.
+pub fn switch_layout(greeter: &Greeter, name: String) {
+  let Some(sender) = greeter.events.clone() else {
+    return;
+  };
+
+  tokio::task::spawn(async move {
+    let result = layout::load_layout(&name).await.map_err(|err| err.to_string());
+
+    let _ = sender.send(Event::Helper(Update::Layout(name, result))).await;
+  });
+}
+
+// Periodically reads the lock state of the console, and sends an update when
+// it changes.
+pub fn watch_locks(sender: Sender<Event>) {
+  tokio::task::spawn(async move {
+    let mut interval = tokio::time::interval(LOCKS_INTERVAL);
+    let mut previous = None;
+
+    loop {
+      interval.tick().await;
+
+      let Some(locks) = console::lock_state() else {
+        break;
+      };
+
+      if previous != Some(locks) {
+        previous = Some(locks);
+
+        if sender.send(Event::Helper(Update::Locks(locks))).await.is_err() {
+          break;
+        }
+      }
+    }
+  });
+}
+
+// Periodically reads the information displayed by dynamic widgets, and sends
+// an update when it changes.
+pub fn watch_system(greeter: &Greeter) {
+  let Some(sender) = greeter.events.clone() else {
+    return;
+  };
+
+  if greeter.settings.issue {
+    let collect = || Issue::collect(true);
+
+    watch(sender.clone(), ISSUE_INTERVAL, greeter.issue.clone(), collect, |issue| Update::Issue(Box::new(issue)));
+  }
+
+  watch(sender, HOSTNAME_INTERVAL, greeter.hostname.clone(), get_hostname, Update::Hostname);
+}
+
+// Calls a function right away and then on an interval, away from the event
+// loop since it may block, and sends an update when its result differs from the
+// last one.
+fn watch<T, F, U>(sender: Sender<Event>, period: Duration, mut previous: T, read: F, update: U)
+where
+  T: PartialEq + Clone + Send + 'static,
+  F: Fn() -> T + Send + Copy + 'static,
+  U: Fn(T) -> Update + Send + 'static,
+{
+  tokio::task::spawn(async move {
+    let mut interval = tokio::time::interval(period);
+
+    loop {
+      interval.tick().await;
+
+      let Ok(value) = tokio::task::spawn_blocking(read).await else {
+        break;
+      };
+
+      if value != previous {
+        previous = value.clone();
+
+        if sender.send(Event::Helper(update(value))).await.is_err() {
+          break;
+        }
+      }
+    }
+  });
+}
+
+// Requests a redraw whenever the displayed time changes, aligned on the start of
+// each tick so the clock does not lag behind. The tick is computed again every
+// time, since the issue, which can show the time, is read in the background.
+pub fn watch_clock(greeter: Arc<RwLock<Greeter>>, sender: Sender<Event>) {
+  tokio::task::spawn(async move {
+    loop {
+      let tick = {
+        let greeter = greeter.read().await;
+
+        clock::tick(&greeter.settings, greeter.issue.as_ref())
+      };
+
+      let elapsed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+      let remaining = tick.as_millis() - elapsed.as_millis() % tick.as_millis();
+
+      tokio::time::sleep(Duration::from_millis(remaining as u64)).await;
+
+      if sender.send(Event::Render).await.is_err() {
+        break;
+      }
+    }
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use super::run_command;
+
+  const TIMEOUT: Duration = Duration::from_secs(5);
+
+  #[tokio::test]
+  async fn commands_are_split_like_a_shell() {
+    let output = run_command(r#"printf  '[%s]'  "two words" one\ arg '' $HOME"#, TIMEOUT).await.unwrap();
+
+    assert_eq!(output, "[two words][one arg][][$HOME]");
+  }
+
+  #[tokio::test]
+  async fn invalid_commands() {
+    let err = run_command("printf 'unterminated", TIMEOUT).await.unwrap_err().to_string();
+    assert!(err.starts_with("could not parse command 'printf 'unterminated'"), "{}", err);
+
+    assert_eq!(run_command("  ", TIMEOUT).await.unwrap_err().to_string(), "command is empty");
+    assert!(run_command("false", TIMEOUT).await.is_err());
+    assert!(run_command("sleep 1", Duration::from_millis(50)).await.unwrap_err().to_string().contains("timed out"));
+  }
+}
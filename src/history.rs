@@ -0,0 +1,140 @@
+use crate::info::{get_user_history, write_user_history};
+
+// Maximum number of commands kept in the history of each user.
+const HISTORY_SIZE: usize = 100;
+
+// Commands previously used by a user, browsed from the command prompt.
+#[derive(Default)]
+pub struct History {
+  // Commands, the most recent last.
+  entries: Vec<String>,
+  // Index of the entry being displayed, if the history is being browsed.
+  position: Option<usize>,
+  // Command being edited before browsing started, restored when moving past
+  // the most recent entry.
+  draft: String,
+}
+
+impl History {
+  pub fn load(username: &str) -> History {
+    History {
+      entries: get_user_history(username),
+      ..Default::default()
+    }
+  }
+
+  // Moves to the previous command, saving the one being edited when browsing
+  // starts.
+  pub fn previous(&mut self, current: &str) -> Option<&str> {
+    let position = match self.position {
+      None if self.entries.is_empty() => return None,
+      None => {
+        self.draft = current.to_string();
+        self.entries.len() - 1
+      }
+
+      Some(0) => return None,
+      Some(position) => position - 1,
+    };
+
+    self.position = Some(position);
+
+    Some(&self.entries[position])
+  }
+
+  // Moves to the next command, or back to the one that was being edited.
+  pub fn next(&mut self) -> Option<&str> {
+    match self.position {
+      None => None,
+
+      Some(position) if position + 1 < self.entries.len() => {
+        self.position = Some(position + 1);
+
+        Some(&self.entries[position + 1])
+      }
+
+      Some(_) => {
+        self.position = None;
+
+        Some(&self.draft)
+      }
+    }
+  }
+}
+
+// Adds a command to the history of a user. A command that was already used is
+// moved to the end instead of being repeated.
+pub fn record(username: &str, command: &str) {
+  let mut entries = get_user_history(username);
+
+  push(&mut entries, command);
+  write_user_history(username, &entries);
+}
+
+fn push(entries: &mut Vec<String>, command: &str) {
+  entries.retain(|entry| entry != command);
+  entries.push(command.to_string());
+
+  let excess = entries.len().saturating_sub(HISTORY_SIZE);
+
+  entries.drain(..excess);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{push, History, HISTORY_SIZE};
+
+  fn history(entries: &[&str]) -> History {
+    History {
+      entries: entries.iter().map(|entry| entry.to_string()).collect(),
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn browsing() {
+    let mut history = history(&["sway", "startx", "bash"]);
+
+    assert_eq!(history.next(), None);
+    assert_eq!(history.previous("draft"), Some("bash"));
+    assert_eq!(history.previous("bash"), Some("startx"));
+    assert_eq!(history.previous("startx"), Some("sway"));
+    assert_eq!(history.previous("sway"), None);
+    assert_eq!(history.next(), Some("startx"));
+    assert_eq!(history.next(), Some("bash"));
+
+    // Moving past the most recent command restores the one being edited.
+    assert_eq!(history.next(), Some("draft"));
+    assert_eq!(history.next(), None);
+    assert_eq!(history.previous("edited"), Some("bash"));
+    assert_eq!(history.next(), Some("edited"));
+  }
+
+  #[test]
+  fn empty_history() {
+    let mut history = history(&[]);
+
+    assert_eq!(history.previous("draft"), None);
+    assert_eq!(history.next(), None);
+  }
+
+  #[test]
+  fn recording() {
+    let mut entries = vec!["sway".to_string(), "startx".to_string()];
+
+    push(&mut entries, "bash");
+    assert_eq!(entries, ["sway", "startx", "bash"]);
+
+    // Commands that were already used are moved to the end.
+    push(&mut entries, "sway");
+    assert_eq!(entries, ["startx", "bash", "sway"]);
+
+    for index in 0..HISTORY_SIZE {
+      push(&mut entries, &format!("command {index}"));
+    }
+
+    assert_eq!(entries.len(), HISTORY_SIZE);
+    assert_eq!(entries.first().map(String::as_str), Some("command 0"));
+    assert_eq!(entries.last().map(String::as_str), Some("command 99"));
+  }
+}
@@ -1,20 +1,23 @@
 use std::{
+  collections::HashSet,
   env,
   error::Error,
   fs::{self, File},
   io::{self, BufRead, BufReader},
   path::{Path, PathBuf},
-  process::Command,
 };
 
-use chrono::Local;
-use ini::Ini;
+use ini::{Ini, ParseOption};
 use lazy_static::lazy_static;
-use nix::sys::utsname;
+use nix::{
+  sys::utsname,
+  unistd::{access, AccessFlags},
+};
 use utmp_rs::{UtmpEntry, UtmpParser};
 use uzers::os::unix::UserExt;
 
 use crate::{
+  desktop,
   ui::{
     common::masked::MaskedString,
     sessions::{Session, SessionType},
@@ -23,10 +26,14 @@ use crate::{
   Greeter,
 };
 
+pub const CACHE_DIR: &str = "/var/cache/tuigreet";
+
 const LAST_USER_USERNAME: &str = "/var/cache/tuigreet/lastuser";
 const LAST_USER_NAME: &str = "/var/cache/tuigreet/lastuser-name";
 const LAST_COMMAND: &str = "/var/cache/tuigreet/lastsession";
 const LAST_SESSION: &str = "/var/cache/tuigreet/lastsession-path";
+const LAST_LAYOUT: &str = "/var/cache/tuigreet/lastlayout";
+const HISTORY: &str = "/var/cache/tuigreet/history";
 
 const DEFAULT_MIN_UID: u16 = 1000;
 const DEFAULT_MAX_UID: u16 = 60000;
@@ -57,54 +64,16 @@ pub fn get_tty() -> String {
   }
 }
 
-pub fn get_issue() -> Option<String> {
-  let (date, time) = {
-    let now = Local::now();
-
-    (now.format("%a %b %_d %Y").to_string(), now.format("%H:%M:%S").to_string())
-  };
-
-  let user_count = match UtmpParser::from_path("/var/run/utmp")
+// Number of users logged in, as recorded in utmp.
+pub fn get_user_count() -> usize {
+  UtmpParser::from_path("/var/run/utmp")
     .map(|utmp| {
-      utmp.into_iter().fold(0, |acc, entry| match entry {
-        Ok(UtmpEntry::UserProcess { .. }) => acc + 1,
-        Ok(UtmpEntry::LoginProcess { .. }) => acc + 1,
-        _ => acc,
-      })
+      utmp
+        .into_iter()
+        .filter(|entry| matches!(entry, Ok(UtmpEntry::UserProcess { .. })))
+        .count()
     })
     .unwrap_or(0)
-  {
-    n if n < 2 => format!("{n} user"),
-    n => format!("{n} users"),
-  };
-
-  let vtnr: usize = env::var("XDG_VTNR").unwrap_or_else(|_| "0".to_string()).parse().unwrap_or(0);
-  let uts = utsname::uname();
-
-  if let Ok(issue) = fs::read_to_string("/etc/issue") {
-    let issue = issue
-      .replace("\\S", "Linux")
-      .replace("\\l", &format!("tty{vtnr}"))
-      .replace("\\d", &date)
-      .replace("\\t", &time)
-      .replace("\\U", &user_count);
-
-    let issue = match uts {
-      Ok(uts) => issue
-        .replace("\\s", uts.sysname().to_str().unwrap_or(""))
-        .replace("\\r", uts.release().to_str().unwrap_or(""))
-        .replace("\\v", uts.version().to_str().unwrap_or(""))
-        .replace("\\n", uts.nodename().to_str().unwrap_or(""))
-        .replace("\\m", uts.machine().to_str().unwrap_or(""))
-        .replace("\\o", uts.domainname().to_str().unwrap_or("")),
-
-      _ => issue,
-    };
-
-    return Some(issue.replace("\\x1b", "\x1b").replace("\\033", "\x1b").replace("\\e", "\x1b").replace(r"\\", r"\"));
-  }
-
-  None
 }
 
 pub fn get_last_user_username() -> Option<String> {
@@ -138,7 +107,7 @@ pub fn get_last_user_name() -> Option<String> {
 }
 
 pub fn write_last_username(username: &MaskedString) {
-  let _ = fs::write(LAST_USER_USERNAME, &username.value);
+  let _ = fs::write(LAST_USER_USERNAME, username.value.as_str());
 
   if let Some(ref name) = username.mask {
     let _ = fs::write(LAST_USER_NAME, name);
@@ -201,6 +170,35 @@ pub fn delete_last_user_command(username: &str) {
   let _ = fs::remove_file(format!("{LAST_COMMAND}-{username}"));
 }
 
+pub fn get_last_user_layout(username: &str) -> Option<String> {
+  let layout = fs::read_to_string(format!("{LAST_LAYOUT}-{username}")).ok()?;
+
+  match layout.trim() {
+    "" => None,
+    layout => Some(layout.to_string()),
+  }
+}
+
+pub fn write_last_user_layout(username: &str, layout: &str) {
+  let _ = fs::write(format!("{LAST_LAYOUT}-{username}"), layout);
+}
+
+pub fn get_user_history(username: &str) -> Vec<String> {
+  match fs::read_to_string(format!("{HISTORY}-{username}")) {
+    Ok(history) => history.lines().filter(|line| !line.trim().is_empty()).map(str::to_string).collect(),
+    Err(_) => vec![],
+  }
+}
+
+pub fn write_user_history(username: &str, commands: &[String]) {
+  let _ = fs::write(format!("{HISTORY}-{username}"), commands.join("\n") + "\n");
+}
+
+// Returns the home directory of a user, if they exist.
+pub fn get_home_dir(username: &str) -> Option<PathBuf> {
+  uzers::get_user_by_name(username).map(|user| user.home_dir().to_path_buf())
+}
+
 pub fn get_users(min_uid: u16, max_uid: u16) -> Vec<User> {
   let users = unsafe { uzers::all_users() };
 
@@ -257,20 +255,37 @@ pub fn get_min_max_uids(min_uid: Option<u16>, max_uid: Option<u16>) -> (u16, u16
   }
 }
 
-pub fn get_sessions(greeter: &Greeter) -> Result<Vec<Session>, Box<dyn Error>> {
-  let paths = if greeter.session_paths.is_empty() {
+// Returns the directories session files should be read from, either from the
+// configuration or from the XDG data directories.
+pub fn get_session_paths(greeter: &Greeter) -> &[(PathBuf, SessionType)] {
+  if greeter.settings.session_paths.is_empty() {
     DEFAULT_SESSION_PATHS.as_ref()
   } else {
-    &greeter.session_paths
-  };
+    &greeter.settings.session_paths
+  }
+}
 
+pub fn get_sessions(greeter: &Greeter) -> Result<Vec<Session>, Box<dyn Error>> {
+  let locale = greeter.locale.to_string();
   let mut files = vec![];
+  let mut seen = HashSet::new();
 
-  for (path, session_type) in paths.iter() {
+  for (path, session_type) in get_session_paths(greeter).iter() {
     tracing::info!("reading {:?} sessions from '{}'", session_type, path.display());
 
     if let Ok(entries) = fs::read_dir(path) {
-      files.extend(entries.flat_map(|entry| entry.map(|entry| load_desktop_file(entry.path(), *session_type))).flatten().flatten());
+      for entry in entries.flatten() {
+        // A session file shadows the ones with the same name in the
+        // directories that come after its own, even if it is hidden.
+        if !seen.insert((*session_type, entry.file_name())) {
+          tracing::info!("ignoring session in '{}': shadowed by an earlier one", entry.path().display());
+          continue;
+        }
+
+        if let Ok(Some(session)) = load_desktop_file(entry.path(), *session_type, &locale) {
+          files.push(session);
+        }
+      }
     }
   }
 
@@ -281,11 +296,19 @@ pub fn get_sessions(greeter: &Greeter) -> Result<Vec<Session>, Box<dyn Error>> {
   Ok(files)
 }
 
-fn load_desktop_file<P>(path: P, session_type: SessionType) -> Result<Option<Session>, Box<dyn Error>>
+pub fn load_desktop_file<P>(path: P, session_type: SessionType, locale: &str) -> Result<Option<Session>, Box<dyn Error>>
 where
   P: AsRef<Path>,
 {
-  let desktop = Ini::load_from_file(path.as_ref())?;
+  // Escapes and quotes are handled as defined by the Desktop Entry
+  // Specification rather than by the INI parser.
+  let options = ParseOption {
+    enabled_quote: false,
+    enabled_escape: false,
+    ..Default::default()
+  };
+
+  let desktop = Ini::load_from_file_opt(path.as_ref(), options)?;
   let section = desktop.section(Some("Desktop Entry")).ok_or("no Desktop Entry section in desktop file")?;
 
   if let Some("true") = section.get("Hidden") {
@@ -297,29 +320,56 @@ where
     return Ok(None);
   }
 
+  if let Some(try_exec) = section.get("TryExec").map(desktop::unescape) {
+    if find_executable(&try_exec).is_none() {
+      tracing::info!("ignoring session in '{}': {} was not found", path.as_ref().display(), try_exec);
+      return Ok(None);
+    }
+  }
+
   let slug = path.as_ref().file_stem().map(|slug| slug.to_string_lossy().to_string());
-  let name = section.get("Name").ok_or("no Name property in desktop file")?;
-  let exec = section.get("Exec").ok_or("no Exec property in desktop file")?;
+  let name = desktop::localized(section, "Name", locale).ok_or("no Name property in desktop file")?;
+  let comment = desktop::localized(section, "Comment", locale).filter(|comment| !comment.trim().is_empty());
+  let exec = section.get("Exec").map(desktop::unescape).ok_or("no Exec property in desktop file")?;
+  let icon = section.get("Icon").map(desktop::unescape);
   let xdg_desktop_names = section.get("DesktopNames").map(str::to_string);
 
+  let entry = desktop::Entry {
+    name: &name,
+    icon: icon.as_deref(),
+    path: path.as_ref(),
+  };
+
+  let command = desktop::shell_join(&desktop::parse_exec(&exec, &entry)?);
+
   tracing::info!("got session '{}' in '{}'", name, path.as_ref().display());
 
   Ok(Some(Session {
     slug,
-    name: name.to_string(),
-    command: exec.to_string(),
+    name,
+    comment,
+    command,
     session_type,
     path: Some(path.as_ref().into()),
     xdg_desktop_names,
   }))
 }
 
-pub fn capslock_status() -> bool {
-  let mut command = Command::new("kbdinfo");
-  command.args(["gkbled", "capslock"]);
+// Looks up an executable, either from its path if it contains a slash, or
+// from the directories listed in `PATH`.
+pub fn find_executable(name: &str) -> Option<PathBuf> {
+  let is_executable = |path: &Path| path.is_file() && access(path, AccessFlags::X_OK).is_ok();
+
+  if name.contains('/') {
+    let path = PathBuf::from(name);
 
-  match command.output() {
-    Ok(output) => output.status.code() == Some(0),
-    Err(_) => false,
+    return is_executable(&path).then_some(path);
   }
+
+  env::var_os("PATH").and_then(|paths| {
+    env::split_paths(&paths)
+      .map(|dir| dir.join(name))
+      .find(|path| is_executable(path))
+  })
 }
+
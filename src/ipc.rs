@@ -9,9 +9,10 @@ use tokio::sync::{
 use crate::{
   event::Event,
   find_kmscon_ancestor,
+  history,
   info::{
-    delete_last_user_command, delete_last_user_session, write_last_user_command, write_last_user_session,
-    write_last_username,
+    delete_last_user_command, delete_last_user_session, write_last_user_command, write_last_user_layout,
+    write_last_user_session, write_last_username,
   },
   macros::SafeDebug,
   ui::sessions::{Session, SessionSource, SessionType},
@@ -125,18 +126,18 @@ impl Ipc {
         if greeter.done {
           tracing::info!("greetd acknowledged session start, exiting");
 
-          if greeter.remember {
+          if greeter.settings.remember {
             tracing::info!("caching last successful username");
 
             write_last_username(&greeter.username);
 
-            if greeter.remember_user_session {
+            if greeter.settings.remember_user_session {
               match greeter.session_source {
                 SessionSource::Command(ref command) => {
                   tracing::info!("caching last user command: {command}");
 
-                  write_last_user_command(&greeter.username.value, command);
-                  delete_last_user_session(&greeter.username.value);
+                  write_last_user_command(greeter.username.value.as_str(), command);
+                  delete_last_user_session(greeter.username.value.as_str());
                 }
 
                 SessionSource::Session(index) => {
@@ -147,8 +148,8 @@ impl Ipc {
                   {
                     tracing::info!("caching last user session: {session_path:?}");
 
-                    write_last_user_session(&greeter.username.value, session_path);
-                    delete_last_user_command(&greeter.username.value);
+                    write_last_user_session(greeter.username.value.as_str(), session_path);
+                    delete_last_user_command(greeter.username.value.as_str());
                   }
                 }
 
@@ -157,6 +158,22 @@ impl Ipc {
             }
           }
 
+          if !greeter.settings.keyboard_layouts.is_empty() {
+            if let Some(ref layout) = greeter.layout {
+              tracing::info!("caching last user keyboard layout: {layout}");
+
+              write_last_user_layout(greeter.username.value.as_str(), layout);
+            }
+          }
+
+          // Commands are kept in the history of the user whether or not their
+          // session is remembered.
+          if let SessionSource::Command(ref command) = greeter.session_source {
+            tracing::info!("adding command to user history: {command}");
+
+            history::record(greeter.username.value.as_str(), command);
+          }
+
           if let Some(ref sender) = greeter.events {
             let _ = sender.send(Event::Exit(AuthStatus::Success)).await;
           }
@@ -224,7 +241,7 @@ impl Ipc {
             greeter.message = Some(fl!("failed"));
             self
               .send(Request::CreateSession {
-                username: greeter.username.value.clone(),
+                username: greeter.username.value.to_string(),
               })
               .await;
             greeter.reset(true).await;
@@ -295,10 +312,10 @@ fn wrap_session_command<'a>(
       }
 
       if *session_type == SessionType::X11 {
-        if let Some(ref wrap) = greeter.xsession_wrapper {
+        if let Some(ref wrap) = greeter.settings.xsession_wrapper {
           return (Cow::Owned(format!("{} {}", wrap, default.command())), env);
         }
-      } else if let Some(ref wrap) = greeter.session_wrapper {
+      } else if let Some(ref wrap) = greeter.settings.session_wrapper {
         return (Cow::Owned(format!("{} {}", wrap, default.command())), env);
       }
     }
@@ -0,0 +1,737 @@
+use std::{
+  collections::HashMap,
+  env,
+  ffi::{CStr, CString},
+  fs, io,
+  net::{IpAddr, ToSocketAddrs},
+  path::Path,
+  ptr,
+};
+
+use chrono::Local;
+use nix::{
+  ifaddrs::getifaddrs,
+  libc,
+  net::if_::InterfaceFlags,
+  sys::{
+    termios::{cfgetospeed, tcgetattr, BaudRate},
+    utsname,
+  },
+};
+
+use crate::info::{get_hostname, get_tty, get_user_count};
+
+// Issue files and drop-in directories, in the order agetty looks them up. The
+// first location that has any is used, except that the drop-ins of
+// `/etc/issue.d` are only read along with `/etc/issue`.
+const LOCATIONS: &[(&str, &str)] = &[
+  ("/etc/issue", "/etc/issue.d"),
+  ("/run/issue", "/run/issue.d"),
+  ("/usr/lib/issue", "/usr/lib/issue.d"),
+];
+
+const OS_RELEASE_FILES: &[&str] = &["/etc/os-release", "/usr/lib/os-release"];
+
+// Escape sequences of `\e{NAME}`, as understood by agetty.
+const COLORS: &[(&str, &str)] = &[
+  ("black", "\x1b[30m"),
+  ("blink", "\x1b[5m"),
+  ("blue", "\x1b[34m"),
+  ("bold", "\x1b[1m"),
+  ("brown", "\x1b[33m"),
+  ("cyan", "\x1b[36m"),
+  ("darkgray", "\x1b[1;30m"),
+  ("gray", "\x1b[37m"),
+  ("green", "\x1b[32m"),
+  ("halfbright", "\x1b[2m"),
+  ("lightblue", "\x1b[1;34m"),
+  ("lightcyan", "\x1b[1;36m"),
+  ("lightgray", "\x1b[1;37m"),
+  ("lightgreen", "\x1b[1;32m"),
+  ("lightmagenta", "\x1b[1;35m"),
+  ("lightred", "\x1b[1;31m"),
+  ("magenta", "\x1b[35m"),
+  ("red", "\x1b[31m"),
+  ("reset", "\x1b[0m"),
+  ("reverse", "\x1b[7m"),
+  ("white", "\x1b[1;37m"),
+  ("yellow", "\x1b[1;33m"),
+];
+
+// Information about the system the escapes of an issue file are replaced with.
+pub trait Facts {
+  fn date(&self) -> String;
+  fn time(&self) -> String;
+  // Name of the terminal line, like `tty1`.
+  fn line(&self) -> String;
+  // Output speed of the terminal line.
+  fn baud_rate(&self) -> Option<u32>;
+  fn uname(&self) -> Uname;
+  fn dns_domain(&self) -> Option<String>;
+  // Value of a variable from `os-release`.
+  fn os_release(&self, name: &str) -> Option<String>;
+  // Number of users currently logged in.
+  fn users(&self) -> usize;
+  // Address of a network interface, or of the first one that is up.
+  fn address(&self, ipv6: bool, interface: Option<&str>) -> Option<IpAddr>;
+}
+
+// Fields of `uname`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Uname {
+  pub sysname: String,
+  pub nodename: String,
+  pub release: String,
+  pub version: String,
+  pub machine: String,
+  pub domainname: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Address {
+  interface: String,
+  ip: IpAddr,
+  // Whether the interface is up and not a loopback, so its address can be
+  // shown when no interface is requested.
+  up: bool,
+}
+
+// Issue files, along with the information about the host their escapes refer
+// to, as it was when they were collected. The date and time are the only
+// values that are read when the issue is rendered.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Issue {
+  template: String,
+  line: String,
+  baud_rate: Option<u32>,
+  uname: Uname,
+  dns_domain: Option<String>,
+  os_release: HashMap<String, String>,
+  users: usize,
+  addresses: Vec<Address>,
+  // Addresses the hostname resolves to, used when no interface is up.
+  resolved: Vec<IpAddr>,
+}
+
+impl Issue {
+  // Reads the issue files, and the information their escapes refer to. Values
+  // that need lookups are only collected if they are used, and those that go
+  // through the resolver, which can block until it times out, only if
+  // `resolve` is set.
+  pub fn collect(resolve: bool) -> Option<Issue> {
+    let template = read_issue()?;
+
+    let mut issue = Issue {
+      line: get_line(),
+      uname: get_uname(),
+      ..Default::default()
+    };
+
+    if uses(&template, "b") {
+      issue.baud_rate = get_baud_rate();
+    }
+
+    if resolve && uses(&template, "O") {
+      issue.dns_domain = get_dns_domain();
+    }
+
+    if uses(&template, "S") {
+      issue.os_release = OS_RELEASE_FILES
+        .iter()
+        .find_map(|path| fs::read_to_string(path).ok())
+        .map(|content| parse_os_release(&content))
+        .unwrap_or_default();
+    }
+
+    if uses(&template, "uU") {
+      issue.users = get_user_count();
+    }
+
+    if uses(&template, "46") {
+      issue.addresses = get_addresses();
+
+      let up = |ipv6: bool| issue.addresses.iter().any(|address| address.up && address.ip.is_ipv6() == ipv6);
+
+      if resolve && (!up(false) || !up(true)) {
+        issue.resolved = (issue.uname.nodename.as_str(), 0)
+          .to_socket_addrs()
+          .map(|addresses| addresses.map(|address| address.ip()).collect())
+          .unwrap_or_default();
+      }
+    }
+
+    issue.template = template;
+
+    Some(issue)
+  }
+
+  pub fn shows_time(&self) -> bool {
+    uses(&self.template, "t")
+  }
+
+  pub fn render(&self) -> String {
+    render(&self.template, self)
+  }
+}
+
+impl Facts for Issue {
+  fn date(&self) -> String {
+    Local::now().format("%a %b %_d %Y").to_string()
+  }
+
+  fn time(&self) -> String {
+    Local::now().format("%H:%M:%S").to_string()
+  }
+
+  fn line(&self) -> String {
+    self.line.clone()
+  }
+
+  fn baud_rate(&self) -> Option<u32> {
+    self.baud_rate
+  }
+
+  fn uname(&self) -> Uname {
+    self.uname.clone()
+  }
+
+  fn dns_domain(&self) -> Option<String> {
+    self.dns_domain.clone()
+  }
+
+  fn os_release(&self, name: &str) -> Option<String> {
+    self.os_release.get(name).cloned()
+  }
+
+  fn users(&self) -> usize {
+    self.users
+  }
+
+  fn address(&self, ipv6: bool, interface: Option<&str>) -> Option<IpAddr> {
+    let addresses: Vec<IpAddr> = self
+      .addresses
+      .iter()
+      .filter(|address| match interface {
+        Some(interface) => address.interface == interface,
+        None => address.up,
+      })
+      .map(|address| address.ip)
+      .filter(|ip| ip.is_ipv6() == ipv6)
+      .collect();
+
+    // Link-local IPv6 addresses are only used when there is nothing better.
+    let address = addresses
+      .iter()
+      .find(|address| !is_link_local(address))
+      .or_else(|| addresses.first())
+      .copied();
+
+    match interface {
+      Some(_) => address,
+
+      // Without any configured interface, agetty falls back to the addresses
+      // the hostname resolves to.
+      None => address.or_else(|| self.resolved.iter().find(|ip| ip.is_ipv6() == ipv6).copied()),
+    }
+  }
+}
+
+// Whether an issue uses any of the provided escapes.
+fn uses(template: &str, escapes: &str) -> bool {
+  let mut chars = template.chars();
+
+  while let Some(c) = chars.next() {
+    if c == '\\' && chars.next().is_some_and(|escape| escapes.contains(escape)) {
+      return true;
+    }
+  }
+
+  false
+}
+
+// Falls back to the virtual terminal logind assigned, when standard input is
+// not a virtual console.
+fn get_line() -> String {
+  let tty = get_tty();
+
+  match env::var("XDG_VTNR") {
+    Ok(vtnr) if !tty.starts_with("tty") => format!("tty{vtnr}"),
+    _ => tty,
+  }
+}
+
+fn get_baud_rate() -> Option<u32> {
+  let termios = tcgetattr(io::stdin()).ok()?;
+
+  baud_rate(cfgetospeed(&termios))
+}
+
+fn get_uname() -> Uname {
+  let Ok(uts) = utsname::uname() else {
+    return Uname::default();
+  };
+
+  let field = |value: &std::ffi::OsStr| value.to_string_lossy().into_owned();
+
+  Uname {
+    sysname: field(uts.sysname()),
+    nodename: field(uts.nodename()),
+    release: field(uts.release()),
+    version: field(uts.version()),
+    machine: field(uts.machine()),
+    domainname: field(uts.domainname()),
+  }
+}
+
+// Domain of the canonical name of the host, as resolved by the system.
+fn get_dns_domain() -> Option<String> {
+  let hostname = CString::new(get_hostname()).ok()?;
+  let hints = libc::addrinfo {
+    ai_flags: libc::AI_CANONNAME,
+    ..unsafe { std::mem::zeroed() }
+  };
+
+  let mut info: *mut libc::addrinfo = ptr::null_mut();
+
+  unsafe {
+    if libc::getaddrinfo(hostname.as_ptr(), ptr::null(), &hints, &mut info) != 0 || info.is_null() {
+      return None;
+    }
+
+    let canonical = match (*info).ai_canonname.is_null() {
+      true => None,
+      false => Some(CStr::from_ptr((*info).ai_canonname).to_string_lossy().into_owned()),
+    };
+
+    libc::freeaddrinfo(info);
+
+    canonical?.split_once('.').map(|(_, domain)| domain.to_string())
+  }
+}
+
+fn get_addresses() -> Vec<Address> {
+  let Ok(addresses) = getifaddrs() else {
+    return Vec::new();
+  };
+
+  addresses
+    .filter_map(|address| {
+      let up = address.flags.contains(InterfaceFlags::IFF_UP | InterfaceFlags::IFF_RUNNING)
+        && !address.flags.contains(InterfaceFlags::IFF_LOOPBACK);
+
+      let storage = address.address?;
+      let ip = match (storage.as_sockaddr_in(), storage.as_sockaddr_in6()) {
+        (Some(address), _) => IpAddr::V4(address.ip()),
+        (_, Some(address)) => IpAddr::V6(address.ip()),
+        _ => return None,
+      };
+
+      Some(Address {
+        interface: address.interface_name,
+        ip,
+        up,
+      })
+    })
+    .collect()
+}
+
+// Reads and concatenates the issue file and its drop-ins, from the first
+// location that has any.
+fn read_issue() -> Option<String> {
+  let mut issue = String::new();
+
+  for (index, (file, dir)) in LOCATIONS.iter().enumerate() {
+    if index == 0 && !Path::new(file).exists() {
+      continue;
+    }
+
+    let mut found = false;
+
+    if let Ok(content) = fs::read_to_string(file) {
+      issue.push_str(&content);
+      found = true;
+    }
+
+    if let Ok(entries) = fs::read_dir(dir) {
+      let mut paths: Vec<_> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "issue") && path.is_file())
+        .collect();
+
+      paths.sort();
+
+      for path in paths {
+        if let Ok(content) = fs::read_to_string(path) {
+          issue.push_str(&content);
+          found = true;
+        }
+      }
+    }
+
+    if found || index == 0 {
+      break;
+    }
+  }
+
+  match issue.is_empty() {
+    true => None,
+    false => Some(issue),
+  }
+}
+
+// Replaces the escapes of an issue with their values, following agetty. Unknown
+// escapes are replaced with the character that follows the backslash.
+pub fn render(issue: &str, facts: &dyn Facts) -> String {
+  let mut output = String::new();
+  let mut chars = issue.chars().peekable();
+
+  while let Some(c) = chars.next() {
+    if c != '\\' {
+      output.push(c);
+      continue;
+    }
+
+    let Some(escape) = chars.next() else {
+      break;
+    };
+
+    // Some escapes take an argument, like `\4{eth0}` or `\S{VERSION}`.
+    let argument = match escape {
+      '4' | '6' | 'e' | 'S' if chars.peek() == Some(&'{') => {
+        chars.next();
+
+        Some(chars.by_ref().take_while(|c| *c != '}').collect::<String>())
+      }
+
+      _ => None,
+    };
+
+    match escape {
+      '4' | '6' => {
+        if let Some(address) = facts.address(escape == '6', argument.as_deref()) {
+          output.push_str(&address.to_string());
+        }
+      }
+
+      'b' => {
+        if let Some(baud_rate) = facts.baud_rate() {
+          output.push_str(&baud_rate.to_string());
+        }
+      }
+
+      'd' => output.push_str(&facts.date()),
+      't' => output.push_str(&facts.time()),
+      'l' => output.push_str(&facts.line()),
+
+      'e' => match argument {
+        Some(name) => {
+          if let Some((_, sequence)) = COLORS.iter().find(|(color, _)| *color == name) {
+            output.push_str(sequence);
+          }
+        }
+
+        None => output.push('\x1b'),
+      },
+
+      's' => output.push_str(&facts.uname().sysname),
+      'n' => output.push_str(&facts.uname().nodename),
+      'r' => output.push_str(&facts.uname().release),
+      'v' => output.push_str(&facts.uname().version),
+      'm' => output.push_str(&facts.uname().machine),
+      'o' => output.push_str(&facts.uname().domainname),
+
+      'O' => output.push_str(&facts.dns_domain().unwrap_or_else(|| "unknown_domain".to_string())),
+
+      'S' => match argument {
+        Some(name) => output.push_str(&facts.os_release(&name).unwrap_or_default()),
+        None => match facts.os_release("PRETTY_NAME") {
+          Some(name) => output.push_str(&name),
+          None => output.push_str(&facts.uname().sysname),
+        },
+      },
+
+      'u' => output.push_str(&facts.users().to_string()),
+      'U' => match facts.users() {
+        1 => output.push_str("1 user"),
+        n => output.push_str(&format!("{n} users")),
+      },
+
+      // Not understood by agetty, but commonly used to color issue files.
+      'x' if chars.clone().take(2).collect::<String>().eq_ignore_ascii_case("1b") => {
+        chars.nth(1);
+        output.push('\x1b');
+      }
+
+      '0' if chars.clone().take(2).eq("33".chars()) => {
+        chars.nth(1);
+        output.push('\x1b');
+      }
+
+      escape => output.push(escape),
+    }
+  }
+
+  output
+}
+
+// Parses the `KEY=VALUE` assignments of an `os-release` file, whose values may
+// be quoted and escaped like in a shell.
+fn parse_os_release(content: &str) -> HashMap<String, String> {
+  content
+    .lines()
+    .map(str::trim)
+    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+    .filter_map(|line| line.split_once('='))
+    .map(|(name, value)| {
+      let value = match value.chars().next() {
+        Some(quote @ ('"' | '\'')) => {
+          let value = value.strip_prefix(quote).unwrap_or(value);
+          let value = value.strip_suffix(quote).unwrap_or(value);
+
+          match quote {
+            '"' => unescape(value),
+            _ => value.to_string(),
+          }
+        }
+
+        _ => value.to_string(),
+      };
+
+      (name.to_string(), value)
+    })
+    .collect()
+}
+
+fn unescape(value: &str) -> String {
+  let mut output = String::new();
+  let mut chars = value.chars();
+
+  while let Some(c) = chars.next() {
+    match c {
+      '\\' => output.extend(chars.next()),
+      c => output.push(c),
+    }
+  }
+
+  output
+}
+
+fn is_link_local(address: &IpAddr) -> bool {
+  match address {
+    IpAddr::V6(address) => address.segments()[0] & 0xffc0 == 0xfe80,
+    IpAddr::V4(_) => false,
+  }
+}
+
+fn baud_rate(speed: BaudRate) -> Option<u32> {
+  let rates = [
+    (BaudRate::B50, 50),
+    (BaudRate::B75, 75),
+    (BaudRate::B110, 110),
+    (BaudRate::B134, 134),
+    (BaudRate::B150, 150),
+    (BaudRate::B200, 200),
+    (BaudRate::B300, 300),
+    (BaudRate::B600, 600),
+    (BaudRate::B1200, 1200),
+    (BaudRate::B1800, 1800),
+    (BaudRate::B2400, 2400),
+    (BaudRate::B4800, 4800),
+    (BaudRate::B9600, 9600),
+    (BaudRate::B19200, 19200),
+    (BaudRate::B38400, 38400),
+    (BaudRate::B57600, 57600),
+    (BaudRate::B115200, 115200),
+    (BaudRate::B230400, 230400),
+    (BaudRate::B460800, 460800),
+    (BaudRate::B500000, 500000),
+    (BaudRate::B576000, 576000),
+    (BaudRate::B921600, 921600),
+    (BaudRate::B1000000, 1000000),
+    (BaudRate::B1152000, 1152000),
+    (BaudRate::B1500000, 1500000),
+    (BaudRate::B2000000, 2000000),
+    (BaudRate::B2500000, 2500000),
+    (BaudRate::B3000000, 3000000),
+    (BaudRate::B3500000, 3500000),
+    (BaudRate::B4000000, 4000000),
+  ];
+
+  rates.iter().find(|(rate, _)| *rate == speed).map(|(_, value)| *value)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::net::IpAddr;
+
+  use super::{parse_os_release, render, uses, Address, Facts, Issue, Uname};
+
+  struct Fake {
+    users: usize,
+  }
+
+  impl Facts for Fake {
+    fn date(&self) -> String {
+      "Sat Oct 17 2026".to_string()
+    }
+
+    fn time(&self) -> String {
+      "21:38:40".to_string()
+    }
+
+    fn line(&self) -> String {
+      "tty1".to_string()
+    }
+
+    fn baud_rate(&self) -> Option<u32> {
+      Some(38400)
+    }
+
+    fn uname(&self) -> Uname {
+      Uname {
+        sysname: "Linux".to_string(),
+        nodename: "vm".to_string(),
+        release: "6.1.0".to_string(),
+        version: "#1 SMP".to_string(),
+        machine: "x86_64".to_string(),
+        domainname: "(none)".to_string(),
+      }
+    }
+
+    fn dns_domain(&self) -> Option<String> {
+      None
+    }
+
+    fn os_release(&self, name: &str) -> Option<String> {
+      match name {
+        "PRETTY_NAME" => Some("Debian GNU/Linux 12 (bookworm)".to_string()),
+        "VERSION_ID" => Some("12".to_string()),
+        _ => None,
+      }
+    }
+
+    fn users(&self) -> usize {
+      self.users
+    }
+
+    fn address(&self, ipv6: bool, interface: Option<&str>) -> Option<IpAddr> {
+      match (ipv6, interface) {
+        (false, None | Some("eth0")) => "192.0.2.10".parse().ok(),
+        (true, None) => "2001:db8::10".parse().ok(),
+        _ => None,
+      }
+    }
+  }
+
+  fn check(issue: &str, expected: &str) {
+    assert_eq!(render(issue, &Fake { users: 2 }), expected, "{}", issue);
+  }
+
+  #[test]
+  fn addresses() {
+    check("\\4", "192.0.2.10");
+    check("\\4{eth0}", "192.0.2.10");
+    check("[\\4{wlan0}]", "[]");
+    check("\\6", "2001:db8::10");
+    check("\\6{eth0}", "");
+  }
+
+  #[test]
+  fn terminal_line() {
+    check("\\l at \\b bauds", "tty1 at 38400 bauds");
+  }
+
+  #[test]
+  fn date_and_time() {
+    check("\\d \\t", "Sat Oct 17 2026 21:38:40");
+  }
+
+  #[test]
+  fn colors() {
+    check("\\e[1m", "\x1b[1m");
+    check("\\e{red}x\\e{reset}", "\x1b[31mx\x1b[0m");
+    check("\\e{mauve}x", "x");
+    check("\\x1b[1mx\\033[0m", "\x1b[1mx\x1b[0m");
+    check("\\x1B[1m", "\x1b[1m");
+    check("\\x2 \\0 \\033", "x2 0 \x1b");
+    check("\\x33", "x33");
+  }
+
+  #[test]
+  fn os_release() {
+    check("\\S", "Debian GNU/Linux 12 (bookworm)");
+    check("\\S{VERSION_ID}", "12");
+    check("\\S{VARIANT}!", "!");
+  }
+
+  #[test]
+  fn system() {
+    check("\\s \\n \\r \\v \\m \\o", "Linux vm 6.1.0 #1 SMP x86_64 (none)");
+    check("\\O", "unknown_domain");
+  }
+
+  #[test]
+  fn users() {
+    check("\\u \\U", "2 2 users");
+    assert_eq!(render("\\U", &Fake { users: 1 }), "1 user");
+  }
+
+  #[test]
+  fn literals() {
+    check("\\\\", "\\");
+    check("\\q", "q");
+    check("trailing \\", "trailing ");
+    check("\\4{eth0", "192.0.2.10");
+    check("\\d{x}", "Sat Oct 17 2026{x}");
+  }
+
+  #[test]
+  fn os_release_values() {
+    let values = parse_os_release(
+      "# comment\nNAME=\"Debian GNU/Linux\"\nVERSION_ID='12'\nID=debian\nHOME_URL=\"https://www.debian.org/\"\nQUOTED=\"a \\\"b\\\" \\$c\"\n",
+    );
+
+    assert_eq!(values["NAME"], "Debian GNU/Linux");
+    assert_eq!(values["VERSION_ID"], "12");
+    assert_eq!(values["ID"], "debian");
+    assert_eq!(values["HOME_URL"], "https://www.debian.org/");
+    assert_eq!(values["QUOTED"], "a \"b\" $c");
+    assert!(!values.contains_key("# comment"));
+  }
+
+  #[test]
+  fn used_escapes() {
+    assert!(uses("\\4{eth0}", "46"));
+    assert!(uses("at \\t", "dt"));
+    assert!(!uses("\\\\4 \\n", "46"));
+    assert!(!uses("trailing \\", "4"));
+  }
+
+  #[test]
+  fn collected_addresses() {
+    let address = |interface: &str, ip: &str, up: bool| Address {
+      interface: interface.to_string(),
+      ip: ip.parse().unwrap(),
+      up,
+    };
+
+    let issue = Issue {
+      addresses: vec![
+        address("lo", "127.0.0.1", false),
+        address("eth0", "fe80::1", true),
+        address("eth0", "2001:db8::1", true),
+        address("wlan0", "192.0.2.20", false),
+      ],
+      resolved: vec!["192.0.2.1".parse().unwrap()],
+      ..Default::default()
+    };
+
+    assert_eq!(issue.address(true, None), "2001:db8::1".parse().ok());
+    assert_eq!(issue.address(false, Some("wlan0")), "192.0.2.20".parse().ok());
+    assert_eq!(issue.address(false, Some("eth0")), None);
+
+    // Without any interface up, the addresses of the hostname are used.
+    assert_eq!(issue.address(false, None), "192.0.2.1".parse().ok());
+  }
+}
@@ -5,15 +5,18 @@ use greetd_ipc::Request;
 use tokio::sync::RwLock;
 
 use crate::{
-  fortune::get_fortune,
+  helpers,
+  history::History,
   info::{
-    delete_last_command, delete_last_session, get_last_user_command, get_last_user_session, write_last_command,
-    write_last_session_path,
+    delete_last_command, delete_last_session, get_home_dir, get_last_user_command, get_last_user_layout,
+    get_last_user_session, write_last_command, write_last_session_path,
   },
   ipc::Ipc,
+  keymap::Action,
+  layout::next_layout,
   power::power,
   ui::{
-    common::masked::MaskedString,
+    common::{editor::Edit, masked::MaskedString, menu::MenuControl},
     sessions::{Session, SessionSource},
     users::User,
   },
@@ -33,91 +36,132 @@ pub async fn handle(greeter: Arc<RwLock<Greeter>>, input: KeyEvent, ipc: Ipc) ->
     return Ok(());
   }
 
-  match input {
-    // ^U should erase the current buffer.
-    KeyEvent {
-      code: KeyCode::Char('u'),
-      modifiers: KeyModifiers::CONTROL,
-      ..
-    } => match greeter.mode {
-      Mode::Username => greeter.username = MaskedString::default(),
-      Mode::Password => greeter.buffer = String::new(),
-      Mode::Command => greeter.buffer = String::new(),
-      _ => {}
-    },
+  // In debug mode only, ^X will exit the application.
+  #[cfg(debug_assertions)]
+  if let KeyEvent {
+    code: KeyCode::Char('x'),
+    modifiers: KeyModifiers::CONTROL,
+    ..
+  } = input
+  {
+    use crate::{AuthStatus, Event};
+
+    if let Some(ref sender) = greeter.events {
+      let _ = sender.send(Event::Exit(AuthStatus::Cancel)).await;
+    }
+
+    return Ok(());
+  }
+
+  let action = greeter.settings.keymap.action(greeter.mode, &input);
+
+  // Menus can be narrowed down by typing. Once a filter is being typed, every
+  // printable character is added to it, even those bound to an action.
+  if let Some(menu) = greeter.menu() {
+    let filtering = !menu.filter().is_empty();
+
+    match input {
+      KeyEvent {
+        code: KeyCode::Char(c),
+        modifiers,
+        ..
+      } if !modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) && (filtering || action.is_none()) => {
+        menu.push_filter(c);
+
+        return Ok(());
+      }
+
+      KeyEvent {
+        code: KeyCode::Backspace,
+        ..
+      } if action.is_none() => {
+        menu.pop_filter();
+
+        return Ok(());
+      }
+
+      _ if filtering && action == Some(Action::Cancel) => {
+        menu.clear_filter();
 
-    // In debug mode only, ^X will exit the application.
-    #[cfg(debug_assertions)]
-    KeyEvent {
-      code: KeyCode::Char('x'),
-      modifiers: KeyModifiers::CONTROL,
-      ..
-    } => {
-      use crate::{AuthStatus, Event};
-
-      if let Some(ref sender) = greeter.events {
-        let _ = sender.send(Event::Exit(AuthStatus::Cancel)).await;
+        return Ok(());
       }
+
+      _ => {}
     }
+  }
+
+  match action {
+    Some(action) => run_action(&mut greeter, action, &ipc).await,
+
+    None => match input {
+      // Do not handle any other controls keybindings
+      KeyEvent {
+        modifiers: KeyModifiers::CONTROL,
+        ..
+      } => {}
 
-    // Depending on the active screen, pressing Escape will either return to the
+      // Handle free-form entry of characters.
+      KeyEvent {
+        code: KeyCode::Char(c), ..
+      } => edit(&mut greeter, Edit::Insert(c)),
+
+      _ => {}
+    },
+  }
+
+  Ok(())
+}
+
+// Performs the action a key chord, or a click on a status bar button, is bound
+// to.
+pub async fn run_action(greeter: &mut Greeter, action: Action, ipc: &Ipc) {
+  match action {
+    // Depending on the active screen, cancelling will either return to the
     // previous mode (close a popup, for example), or cancel the `greetd`
     // session.
-    KeyEvent { code: KeyCode::Esc, .. } => match greeter.mode {
-      Mode::Command => {
-        let previous_mode = greeter.previous_mode;
-        greeter.set_mode(previous_mode);
-        greeter.buffer = greeter.previous_buffer.take().unwrap_or_default();
-        greeter.cursor_offset = 0;
+    Action::Cancel => match greeter.mode {
+      // Leaving the completion dropdown restores what was typed.
+      Mode::Command if greeter.completion.selected.is_some() => {
+        let (command, cursor) = greeter.completion.revert();
+
+        greeter.command.set_with_cursor(command, cursor);
       }
 
-      Mode::Users | Mode::Sessions | Mode::Power => {
+      Mode::Command | Mode::Users | Mode::Sessions | Mode::Power => {
         let previous_mode = greeter.previous_mode;
         greeter.set_mode(previous_mode);
       }
 
       _ => {
-        Ipc::cancel(&mut greeter).await;
+        Ipc::cancel(greeter).await;
         greeter.reset(false).await;
       }
     },
 
-    // Simple cursor directions in text fields.
-    KeyEvent {
-      code: KeyCode::Left, ..
-    } => greeter.cursor_offset -= 1,
-    KeyEvent {
-      code: KeyCode::Right, ..
-    } => greeter.cursor_offset += 1,
-
-    // F2 will display the command entry prompt. If we are already in one of the
-    // popup screens, we set the previous screen as being the current previous
-    // screen.
-    KeyEvent {
-      code: KeyCode::F(i), ..
-    } if i == greeter.kb_command => {
+    // Display the command entry prompt. If we are already in one of the popup
+    // screens, we set the previous screen as being the current previous screen.
+    Action::OpenCommand => {
       greeter.previous_mode = match greeter.mode {
         Mode::Users | Mode::Command | Mode::Sessions | Mode::Power => greeter.previous_mode,
         _ => greeter.mode,
       };
 
-      // Set the edition buffer to the current command.
-      greeter.previous_buffer = Some(greeter.buffer.clone());
-      greeter.buffer = greeter
-        .session_source
-        .command(&greeter)
-        .map(str::to_string)
-        .unwrap_or_default();
-      greeter.cursor_offset = 0;
+      // Start editing from the current command.
+      let command = greeter.session_source.command(greeter).map(str::to_string).unwrap_or_default();
+
+      greeter.command.set(command);
+      greeter.history = match greeter.username.value.is_empty() {
+        true => History::default(),
+        false => History::load(greeter.username.value.as_str()),
+      };
+
+      suggest(greeter);
       greeter.set_mode(Mode::Command);
     }
 
-    // F3 will display the session selection menu. If we are already in one of
-    // the popup screens, we set the previous screen as being the current
-    // previous screen.
-    KeyEvent {
-      code: KeyCode::F(i), ..
-    } if i == greeter.kb_sessions => {
+    // Display the session selection menu. If we are already in one of the popup
+    // screens, we set the previous screen as being the current previous screen.
+    Action::OpenSessions => {
       greeter.previous_mode = match greeter.mode {
         Mode::Users | Mode::Command | Mode::Sessions | Mode::Power => greeter.previous_mode,
         _ => greeter.mode,
@@ -126,19 +170,18 @@ pub async fn handle(greeter: Arc<RwLock<Greeter>>, input: KeyEvent, ipc: Ipc) ->
       greeter.set_mode(Mode::Sessions);
     }
 
-    KeyEvent {
-      code: KeyCode::F(i), ..
-    } if i == greeter.kb_fortune => {
-      greeter.fortune = get_fortune();
-      greeter.clear_request = true;
+    Action::RefreshFortune => helpers::refresh_fortune(greeter),
+
+    // Cycle through the configured keyboard layouts.
+    Action::SwitchLayout => {
+      if let Some(layout) = next_layout(&greeter.settings.keyboard_layouts, greeter.layout.as_deref()).cloned() {
+        helpers::switch_layout(greeter, layout);
+      }
     }
 
-    // F12 will display the user selection menu. If we are already in one of the
-    // popup screens, we set the previous screen as being the current previous
-    // screen.
-    KeyEvent {
-      code: KeyCode::F(i), ..
-    } if i == greeter.kb_power => {
+    // Display the power menu. If we are already in one of the popup screens, we
+    // set the previous screen as being the current previous screen.
+    Action::OpenPower => {
       greeter.previous_mode = match greeter.mode {
         Mode::Users | Mode::Command | Mode::Sessions | Mode::Power => greeter.previous_mode,
         _ => greeter.mode,
@@ -147,96 +190,70 @@ pub async fn handle(greeter: Arc<RwLock<Greeter>>, input: KeyEvent, ipc: Ipc) ->
       greeter.set_mode(Mode::Power);
     }
 
-    // Handle moving up in menus.
-    KeyEvent {
-      code: KeyCode::Char('k'),
-      ..
-    } => {
-      if let Mode::Users = greeter.mode {
-        if greeter.users.selected > 0 {
-          greeter.users.selected -= 1;
-        }
-      }
-
-      if let Mode::Sessions = greeter.mode {
-        if greeter.sessions.selected > 0 {
-          greeter.sessions.selected -= 1;
-        }
-      }
+    // Menu navigation, shared by every menu.
+    Action::MenuUp => with_menu(greeter, |menu| menu.up()),
+    Action::MenuDown => with_menu(greeter, |menu| menu.down()),
+    Action::MenuPageUp => with_menu(greeter, |menu| menu.page_up()),
+    Action::MenuPageDown => with_menu(greeter, |menu| menu.page_down()),
+    Action::MenuFirst => with_menu(greeter, |menu| menu.first()),
+    Action::MenuLast => with_menu(greeter, |menu| menu.last()),
+
+    // Line edition, shared by every text prompt.
+    Action::CursorLeft => edit(greeter, Edit::Left),
+    Action::CursorRight => edit(greeter, Edit::Right),
+    Action::WordLeft => edit(greeter, Edit::WordLeft),
+    Action::WordRight => edit(greeter, Edit::WordRight),
+    Action::LineStart => edit(greeter, Edit::Start),
+    Action::LineEnd => edit(greeter, Edit::End),
+    Action::EraseLine => edit(greeter, Edit::KillLine),
+    Action::DeleteBackward => edit(greeter, Edit::DeleteBackward),
+    Action::DeleteForward => edit(greeter, Edit::DeleteForward),
+    Action::KillWordBackward => edit(greeter, Edit::KillWordBackward),
+    Action::KillWordForward => edit(greeter, Edit::KillWordForward),
+    Action::KillToEnd => edit(greeter, Edit::KillToEnd),
+    Action::Yank => edit(greeter, Edit::Yank),
+    Action::YankPop => edit(greeter, Edit::YankPop),
+
+    // Completing the username entry validates it (same as Enter).
+    Action::Complete => match greeter.mode {
+      Mode::Username if !greeter.username.value.is_empty() => validate_username(greeter, ipc).await,
+      Mode::Command => complete(greeter, true),
+      _ => {}
+    },
 
-      if let Mode::Power = greeter.mode {
-        if greeter.powers.selected > 0 {
-          greeter.powers.selected -= 1;
-        }
+    Action::CompletePrevious => {
+      if greeter.mode == Mode::Command {
+        complete(greeter, false);
       }
     }
 
-    // Handle moving down in menus.
-    KeyEvent {
-      code: KeyCode::Char('j'),
-      ..
-    } => {
-      if let Mode::Users = greeter.mode {
-        if greeter.users.selected < greeter.users.options.len() - 1 {
-          greeter.users.selected += 1;
-        }
-      }
+    // Browse the commands previously used by the user.
+    Action::HistoryPrevious | Action::HistoryNext => {
+      if greeter.mode == Mode::Command {
+        let current = greeter.command.to_string();
 
-      if let Mode::Sessions = greeter.mode {
-        if greeter.sessions.selected < greeter.sessions.options.len() - 1 {
-          greeter.sessions.selected += 1;
-        }
-      }
+        let entry = match action {
+          Action::HistoryPrevious => greeter.history.previous(&current),
+          _ => greeter.history.next(),
+        };
 
-      if let Mode::Power = greeter.mode {
-        if greeter.powers.selected < greeter.powers.options.len() - 1 {
-          greeter.powers.selected += 1;
+        if let Some(entry) = entry.map(str::to_string) {
+          greeter.command.set(entry);
+          suggest(greeter);
         }
       }
     }
 
-    // ^A should go to the start of the current prompt
-    KeyEvent {
-      code: KeyCode::Char('a'),
-      modifiers: KeyModifiers::CONTROL,
-      ..
-    } => {
-      let value = {
-        match greeter.mode {
-          Mode::Username => &greeter.username.value,
-          _ => &greeter.buffer,
-        }
-      };
-
-      greeter.cursor_offset = -(value.chars().count() as i16);
-    }
-
-    // ^A should go to the end of the current prompt
-    KeyEvent {
-      code: KeyCode::Char('e'),
-      modifiers: KeyModifiers::CONTROL,
-      ..
-    } => greeter.cursor_offset = 0,
+    // Validate the current entry, depending on the active mode.
+    Action::Validate => match greeter.mode {
+      Mode::Username if !greeter.username.value.is_empty() => validate_username(greeter, ipc).await,
 
-    // Tab should validate the username entry (same as Enter).
-    KeyEvent { code: KeyCode::Tab, .. } => match greeter.mode {
-      Mode::Username if !greeter.username.value.is_empty() => validate_username(&mut greeter, &ipc).await,
-      _ => {}
-    },
-
-    // Enter validates the current entry, depending on the active mode.
-    KeyEvent {
-      code: KeyCode::Enter, ..
-    } => match greeter.mode {
-      Mode::Username if !greeter.username.value.is_empty() => validate_username(&mut greeter, &ipc).await,
-
-      Mode::Username if greeter.user_menu => {
+      Mode::Username if greeter.settings.user_menu => {
         greeter.previous_mode = match greeter.mode {
           Mode::Users | Mode::Command | Mode::Sessions | Mode::Power => greeter.previous_mode,
           _ => greeter.mode,
         };
 
-        greeter.buffer = greeter.previous_buffer.take().unwrap_or_default();
         greeter.set_mode(Mode::Users);
       }
 
@@ -248,45 +265,44 @@ pub async fn handle(greeter: Arc<RwLock<Greeter>>, input: KeyEvent, ipc: Ipc) ->
 
         ipc
           .send(Request::PostAuthMessageResponse {
-            response: Some(greeter.buffer.clone()),
+            response: Some(greeter.answer.to_string()),
           })
           .await;
 
-        greeter.buffer = String::new();
+        greeter.answer.zeroize();
       }
 
       Mode::Command => {
         greeter.sessions.selected = 0;
-        greeter.session_source = SessionSource::Command(greeter.buffer.clone());
+        greeter.session_source = SessionSource::Command(greeter.command.to_string());
 
-        if greeter.remember_session {
-          write_last_command(&greeter.buffer);
+        if greeter.settings.remember_session {
+          write_last_command(greeter.command.as_str());
           delete_last_session();
         }
 
-        greeter.buffer = greeter.previous_buffer.take().unwrap_or_default();
         let previous_mode = greeter.previous_mode;
         greeter.set_mode(previous_mode);
       }
 
       Mode::Users => {
-        let username = greeter.users.options.get(greeter.users.selected).cloned();
+        let username = greeter.users.selected().cloned();
 
         if let Some(User { username, name }) = username {
           greeter.username = MaskedString::from(username, name);
-        }
 
-        let previous_mode = greeter.previous_mode;
-        greeter.set_mode(previous_mode);
+          let previous_mode = greeter.previous_mode;
+          greeter.set_mode(previous_mode);
 
-        validate_username(&mut greeter, &ipc).await;
+          validate_username(greeter, ipc).await;
+        }
       }
 
       Mode::Sessions => {
-        let session = greeter.sessions.options.get(greeter.sessions.selected).cloned();
+        let session = greeter.sessions.selected().cloned();
 
         if let Some(Session { path, .. }) = session {
-          if greeter.remember_session {
+          if greeter.settings.remember_session {
             if let Some(ref path) = path {
               write_last_session_path(path);
               delete_last_command();
@@ -301,10 +317,10 @@ pub async fn handle(greeter: Arc<RwLock<Greeter>>, input: KeyEvent, ipc: Ipc) ->
       }
 
       Mode::Power => {
-        let power_command = greeter.powers.options.get(greeter.powers.selected).cloned();
+        let power_command = greeter.powers.selected().cloned();
 
         if let Some(command) = power_command {
-          power(&mut greeter, command.action).await;
+          power(greeter, command.action).await;
         }
 
         let previous_mode = greeter.previous_mode;
@@ -313,96 +329,52 @@ pub async fn handle(greeter: Arc<RwLock<Greeter>>, input: KeyEvent, ipc: Ipc) ->
 
       _ => {}
     },
+  }
+}
 
-    KeyEvent {
-      code: KeyCode::Char('h'),
-      modifiers: KeyModifiers::CONTROL,
-      ..
-    } => delete_key(&mut greeter, KeyCode::Backspace).await,
-
-    // Do not handle any other controls keybindings
-    KeyEvent {
-      modifiers: KeyModifiers::CONTROL,
-      ..
-    } => {}
-
-    // Handle free-form entry of characters.
-    KeyEvent {
-      code: KeyCode::Char(c), ..
-    } => insert_key(&mut greeter, c).await,
-
-    // Handle deletion of characters.
-    KeyEvent {
-      code: KeyCode::Backspace,
-      ..
-    }
-    | KeyEvent {
-      code: KeyCode::Delete, ..
-    } => delete_key(&mut greeter, input.code).await,
+fn with_menu<F>(greeter: &mut Greeter, action: F)
+where
+  F: FnOnce(&mut dyn MenuControl),
+{
+  if let Some(menu) = greeter.menu() {
+    action(menu);
+  }
+}
 
-    _ => {}
+// Applies an edit to the prompt of the current mode.
+fn edit(greeter: &mut Greeter, edit: Edit) {
+  if let Some(editor) = greeter.editor() {
+    editor.apply(edit);
   }
 
-  Ok(())
+  if greeter.mode == Mode::Command {
+    suggest(greeter);
+  }
 }
 
-// Handle insertion of characters into the proper buffer, depending on the
-// current mode and the position of the cursor.
-async fn insert_key(greeter: &mut Greeter, c: char) {
-  let value = match greeter.mode {
-    Mode::Username => &greeter.username.value,
-    Mode::Password => &greeter.buffer,
-    Mode::Command => &greeter.buffer,
-    _ => return,
-  };
-
-  let index = (value.chars().count() as i16 + greeter.cursor_offset) as usize;
-  let left = value.chars().take(index);
-  let right = value.chars().skip(index);
-
-  let value = left.chain(vec![c].into_iter()).chain(right).collect();
-  let mode = greeter.mode;
-
-  match mode {
-    Mode::Username => greeter.username.value = value,
-    Mode::Password => greeter.buffer = value,
-    Mode::Command => greeter.buffer = value,
-    _ => {}
-  };
+// Lists the sessions matching the command being edited.
+fn suggest(greeter: &mut Greeter) {
+  let (command, cursor) = (greeter.command.as_str(), greeter.command.cursor());
+
+  greeter.completion.suggest(command, cursor, &greeter.sessions.options);
 }
 
-// Handle deletion of characters from a prompt into the proper buffer, depending
-// on the current mode, whether Backspace or Delete was pressed and the position
-// of the cursor.
-async fn delete_key(greeter: &mut Greeter, key: KeyCode) {
-  let value = match greeter.mode {
-    Mode::Username => &greeter.username.value,
-    Mode::Password => &greeter.buffer,
-    Mode::Command => &greeter.buffer,
-    _ => return,
-  };
-
-  let index = match key {
-    KeyCode::Backspace => (value.chars().count() as i16 + greeter.cursor_offset - 1) as usize,
-    KeyCode::Delete => (value.chars().count() as i16 + greeter.cursor_offset) as usize,
-    _ => 0,
-  };
-
-  if value.chars().nth(index).is_some() {
-    let left = value.chars().take(index);
-    let right = value.chars().skip(index + 1);
-
-    let value = left.chain(right).collect();
-
-    match greeter.mode {
-      Mode::Username => greeter.username.value = value,
-      Mode::Password => greeter.buffer = value,
-      Mode::Command => greeter.buffer = value,
-      _ => return,
-    };
-
-    if let KeyCode::Delete = key {
-      greeter.cursor_offset += 1;
+// Applies the next or previous completion candidate to the command. Candidates
+// are looked up when none is selected yet, and a single candidate is accepted
+// right away, so completion can continue from it.
+fn complete(greeter: &mut Greeter, forward: bool) {
+  if greeter.completion.selected.is_none() {
+    let (command, cursor) = (greeter.command.as_str(), greeter.command.cursor());
+    let home = get_home_dir(greeter.username.value.as_str());
+
+    greeter.completion.complete(command, cursor, &greeter.sessions.options, home.as_deref());
+  }
+
+  if let Some((command, cursor)) = greeter.completion.cycle(forward) {
+    greeter.command.set_with_cursor(command, cursor);
+
+    if greeter.completion.candidates.len() == 1 {
+      suggest(greeter);
     }
   }
 }
@@ -414,13 +386,23 @@ async fn validate_username(greeter: &mut Greeter, ipc: &Ipc) {
 
   ipc
     .send(Request::CreateSession {
-      username: greeter.username.value.clone(),
+      username: greeter.username.value.to_string(),
     })
     .await;
-  greeter.buffer = String::new();
+  greeter.answer.zeroize();
+
+  if !greeter.settings.keyboard_layouts.is_empty() {
+    if let Some(layout) = get_last_user_layout(greeter.username.value.as_str()) {
+      if greeter.layout.as_ref() != Some(&layout) {
+        tracing::info!("remembered user keyboard layout is {}", layout);
+
+        helpers::switch_layout(greeter, layout);
+      }
+    }
+  }
 
-  if greeter.remember_user_session {
-    if let Ok(last_session) = get_last_user_session(&greeter.username.value) {
+  if greeter.settings.remember_user_session {
+    if let Ok(last_session) = get_last_user_session(greeter.username.value.as_str()) {
       if let Some(last_session) = Session::from_path(greeter, last_session).cloned() {
         tracing::info!("remembered user session is {}", last_session.name);
 
@@ -434,7 +416,7 @@ async fn validate_username(greeter: &mut Greeter, ipc: &Ipc) {
       }
     }
 
-    if let Ok(command) = get_last_user_command(&greeter.username.value) {
+    if let Ok(command) = get_last_user_command(greeter.username.value.as_str()) {
       tracing::info!("remembered user command is {}", command);
 
       greeter.session_source = SessionSource::Command(command);
@@ -0,0 +1,604 @@
+use std::{
+  error::Error,
+  fmt::{self, Display},
+  str::FromStr,
+};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::{config::Config, Mode};
+
+// Something a key chord can be bound to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+  OpenCommand,
+  OpenSessions,
+  RefreshFortune,
+  SwitchLayout,
+  OpenPower,
+  Cancel,
+  Validate,
+  Complete,
+  CompletePrevious,
+  HistoryPrevious,
+  HistoryNext,
+  CursorLeft,
+  CursorRight,
+  WordLeft,
+  WordRight,
+  LineStart,
+  LineEnd,
+  EraseLine,
+  DeleteBackward,
+  DeleteForward,
+  KillWordBackward,
+  KillWordForward,
+  KillToEnd,
+  Yank,
+  YankPop,
+  MenuUp,
+  MenuDown,
+  MenuPageUp,
+  MenuPageDown,
+  MenuFirst,
+  MenuLast,
+}
+
+// Group of bindings that are active together. Global bindings are active in
+// every mode, but are shadowed by the table of the current mode.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Table {
+  Global,
+  Prompt,
+  Menu,
+}
+
+const ACTIONS: &[(Action, &str, Table)] = &[
+  (Action::OpenCommand, "open-command", Table::Global),
+  (Action::OpenSessions, "open-sessions", Table::Global),
+  (Action::RefreshFortune, "refresh-fortune", Table::Global),
+  (Action::SwitchLayout, "switch-layout", Table::Global),
+  (Action::OpenPower, "open-power", Table::Global),
+  (Action::Cancel, "cancel", Table::Global),
+  (Action::Validate, "validate", Table::Global),
+  (Action::Complete, "complete", Table::Prompt),
+  (Action::CompletePrevious, "complete-previous", Table::Prompt),
+  (Action::HistoryPrevious, "history-previous", Table::Prompt),
+  (Action::HistoryNext, "history-next", Table::Prompt),
+  (Action::CursorLeft, "cursor-left", Table::Prompt),
+  (Action::CursorRight, "cursor-right", Table::Prompt),
+  (Action::WordLeft, "word-left", Table::Prompt),
+  (Action::WordRight, "word-right", Table::Prompt),
+  (Action::LineStart, "line-start", Table::Prompt),
+  (Action::LineEnd, "line-end", Table::Prompt),
+  (Action::EraseLine, "erase-line", Table::Prompt),
+  (Action::DeleteBackward, "delete-backward", Table::Prompt),
+  (Action::DeleteForward, "delete-forward", Table::Prompt),
+  (Action::KillWordBackward, "kill-word-backward", Table::Prompt),
+  (Action::KillWordForward, "kill-word-forward", Table::Prompt),
+  (Action::KillToEnd, "kill-to-end", Table::Prompt),
+  (Action::Yank, "yank", Table::Prompt),
+  (Action::YankPop, "yank-pop", Table::Prompt),
+  (Action::MenuUp, "menu-up", Table::Menu),
+  (Action::MenuDown, "menu-down", Table::Menu),
+  (Action::MenuPageUp, "menu-page-up", Table::Menu),
+  (Action::MenuPageDown, "menu-page-down", Table::Menu),
+  (Action::MenuFirst, "menu-first", Table::Menu),
+  (Action::MenuLast, "menu-last", Table::Menu),
+];
+
+const DEFAULT_BINDINGS: &[(Action, &[&str])] = &[
+  (Action::OpenCommand, &["F2"]),
+  (Action::OpenSessions, &["F3"]),
+  (Action::RefreshFortune, &["F6"]),
+  (Action::SwitchLayout, &["F5"]),
+  (Action::OpenPower, &["F12"]),
+  (Action::Cancel, &["esc"]),
+  (Action::Validate, &["enter"]),
+  (Action::Complete, &["tab"]),
+  (Action::CompletePrevious, &["backtab"]),
+  (Action::HistoryPrevious, &["up", "ctrl+p"]),
+  (Action::HistoryNext, &["down", "ctrl+n"]),
+  (Action::CursorLeft, &["left", "ctrl+b"]),
+  (Action::CursorRight, &["right", "ctrl+f"]),
+  (Action::WordLeft, &["alt+b", "ctrl+left"]),
+  (Action::WordRight, &["alt+f", "ctrl+right"]),
+  (Action::LineStart, &["ctrl+a", "home"]),
+  (Action::LineEnd, &["ctrl+e", "end"]),
+  (Action::EraseLine, &["ctrl+u"]),
+  (Action::DeleteBackward, &["backspace", "ctrl+h"]),
+  (Action::DeleteForward, &["delete", "ctrl+d"]),
+  (Action::KillWordBackward, &["ctrl+w", "alt+backspace"]),
+  (Action::KillWordForward, &["alt+d"]),
+  (Action::KillToEnd, &["ctrl+k"]),
+  (Action::Yank, &["ctrl+y"]),
+  (Action::YankPop, &["alt+y"]),
+  (Action::MenuUp, &["up", "k"]),
+  (Action::MenuDown, &["down", "j"]),
+  (Action::MenuPageUp, &["pageup"]),
+  (Action::MenuPageDown, &["pagedown"]),
+  (Action::MenuFirst, &["home"]),
+  (Action::MenuLast, &["end"]),
+];
+
+// Named keys accepted in chords, besides F-keys and single characters.
+const KEYS: &[(&str, KeyCode)] = &[
+  ("esc", KeyCode::Esc),
+  ("enter", KeyCode::Enter),
+  ("tab", KeyCode::Tab),
+  ("backtab", KeyCode::BackTab),
+  ("backspace", KeyCode::Backspace),
+  ("delete", KeyCode::Delete),
+  ("insert", KeyCode::Insert),
+  ("left", KeyCode::Left),
+  ("right", KeyCode::Right),
+  ("up", KeyCode::Up),
+  ("down", KeyCode::Down),
+  ("home", KeyCode::Home),
+  ("end", KeyCode::End),
+  ("pageup", KeyCode::PageUp),
+  ("pagedown", KeyCode::PageDown),
+  ("space", KeyCode::Char(' ')),
+];
+
+impl Action {
+  pub fn name(&self) -> &'static str {
+    ACTIONS.iter().find(|(action, _, _)| action == self).map(|(_, name, _)| *name).unwrap_or_default()
+  }
+
+  fn table(&self) -> Table {
+    ACTIONS.iter().find(|(action, _, _)| action == self).map(|(_, _, table)| *table).unwrap_or(Table::Global)
+  }
+}
+
+impl FromStr for Action {
+  type Err = String;
+
+  fn from_str(name: &str) -> Result<Action, String> {
+    ACTIONS
+      .iter()
+      .find(|(_, action, _)| *action == name)
+      .map(|(action, _, _)| *action)
+      .ok_or_else(|| format!("unknown action '{name}'"))
+  }
+}
+
+impl Table {
+  fn of(mode: Mode) -> Table {
+    match mode {
+      Mode::Users | Mode::Sessions | Mode::Power => Table::Menu,
+      _ => Table::Prompt,
+    }
+  }
+}
+
+// A key, along with the modifiers that must be held with it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Chord {
+  code: KeyCode,
+  modifiers: KeyModifiers,
+}
+
+impl Chord {
+  fn matches(&self, key: &KeyEvent) -> bool {
+    let modifiers = match key.code {
+      // Shift is already reflected in the character or key itself.
+      KeyCode::Char(_) | KeyCode::BackTab => key.modifiers - KeyModifiers::SHIFT,
+      _ => key.modifiers,
+    };
+
+    let code = match (self.code, key.code) {
+      // Control chords are matched regardless of the case of the character.
+      (KeyCode::Char(_), KeyCode::Char(c)) if modifiers.contains(KeyModifiers::CONTROL) => KeyCode::Char(c.to_ascii_lowercase()),
+      (_, code) => code,
+    };
+
+    self.code == code && self.modifiers == modifiers
+  }
+
+  // Whether the chord would prevent a character from being typed in a prompt.
+  fn is_printable(&self) -> bool {
+    matches!(self.code, KeyCode::Char(_)) && !self.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+  }
+}
+
+impl FromStr for Chord {
+  type Err = String;
+
+  // Parses chords such as `ctrl+p`, `alt+s`, `F5` or `k`, as well as the
+  // notation they are displayed with, such as `^P` or `M-s`.
+  fn from_str(spec: &str) -> Result<Chord, String> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = spec;
+
+    loop {
+      let (prefix, modifier) = match rest {
+        _ if rest.len() > 1 && rest.starts_with('^') => ("^", KeyModifiers::CONTROL),
+        _ if rest.len() > 2 && rest.starts_with("M-") => ("M-", KeyModifiers::ALT),
+        _ if rest.len() > 2 && rest.starts_with("S-") => ("S-", KeyModifiers::SHIFT),
+        _ => break,
+      };
+
+      modifiers |= modifier;
+      rest = &rest[prefix.len()..];
+    }
+
+    let mut parts: Vec<&str> = rest.split('+').collect();
+
+    // Allows binding the `+` character itself.
+    if rest.ends_with("++") || rest == "+" {
+      parts.truncate(parts.len().saturating_sub(2));
+      parts.push("+");
+    }
+
+    let key = parts.pop().filter(|key| !key.is_empty()).ok_or_else(|| format!("invalid key chord '{spec}'"))?;
+
+    for modifier in parts {
+      modifiers |= match modifier.to_lowercase().as_str() {
+        "ctrl" | "control" => KeyModifiers::CONTROL,
+        "alt" | "meta" => KeyModifiers::ALT,
+        "shift" => KeyModifiers::SHIFT,
+        _ => return Err(format!("invalid modifier '{modifier}' in key chord '{spec}'")),
+      };
+    }
+
+    let lowercase = key.to_lowercase();
+
+    let code = if let Some((_, code)) = KEYS.iter().find(|(name, _)| *name == lowercase) {
+      *code
+    } else if let Some(n) = lowercase.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+      match n {
+        1..=12 => KeyCode::F(n),
+        _ => return Err(format!("invalid F-key in key chord '{spec}': must be between 1 and 12")),
+      }
+    } else {
+      let mut chars = key.chars();
+
+      match (chars.next(), chars.next()) {
+        (Some(c), None) if modifiers.contains(KeyModifiers::CONTROL) => KeyCode::Char(c.to_ascii_lowercase()),
+        (Some(c), None) => KeyCode::Char(c),
+        _ => return Err(format!("unknown key '{key}' in key chord '{spec}'")),
+      }
+    };
+
+    // Shifted characters are matched by the character they produce.
+    if let KeyCode::Char(c) = code {
+      if modifiers.contains(KeyModifiers::SHIFT) {
+        return Err(format!("invalid key chord '{spec}': use '{}' instead of shift", c.to_uppercase()));
+      }
+    }
+
+    Ok(Chord { code, modifiers })
+  }
+}
+
+impl Display for Chord {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    if self.modifiers.contains(KeyModifiers::CONTROL) {
+      write!(f, "^")?;
+    }
+    if self.modifiers.contains(KeyModifiers::ALT) {
+      write!(f, "M-")?;
+    }
+    if self.modifiers.contains(KeyModifiers::SHIFT) {
+      write!(f, "S-")?;
+    }
+
+    match self.code {
+      KeyCode::F(n) => write!(f, "F{n}"),
+      KeyCode::Char(' ') => write!(f, "Space"),
+      KeyCode::Char(c) if self.modifiers.contains(KeyModifiers::CONTROL) => write!(f, "{}", c.to_ascii_uppercase()),
+      KeyCode::Char(c) => write!(f, "{c}"),
+
+      code => match KEYS.iter().find(|(_, key)| *key == code) {
+        Some((name, _)) => {
+          let mut name = name.chars();
+
+          match name.next() {
+            Some(first) => write!(f, "{}{}", first.to_ascii_uppercase(), name.as_str()),
+            None => Ok(()),
+          }
+        }
+
+        None => write!(f, "?"),
+      },
+    }
+  }
+}
+
+struct Binding {
+  chord: Chord,
+  action: Action,
+  // Where the binding was defined, for error messages.
+  origin: String,
+}
+
+// Maps key chords to actions, per mode.
+#[derive(Default)]
+pub struct Keymap {
+  bindings: Vec<Binding>,
+}
+
+impl Keymap {
+  // Builds the keymap from the default bindings, the `--kb-*` F-key shorthands
+  // and the `--keybinding` options, in that order of precedence.
+  pub fn from_config(config: &Config, layouts: bool) -> Result<Keymap, Box<dyn Error>> {
+    let mut keymap = Keymap::default();
+
+    for (action, chords) in DEFAULT_BINDINGS {
+      // The layout binding is only active when layouts are configured.
+      if *action == Action::SwitchLayout && !layouts {
+        continue;
+      }
+
+      for chord in chords.iter() {
+        keymap.bindings.push(Binding {
+          chord: chord.parse()?,
+          action: *action,
+          origin: "default".to_string(),
+        });
+      }
+    }
+
+    for (name, action) in [
+      ("kb-command", Action::OpenCommand),
+      ("kb-sessions", Action::OpenSessions),
+      ("kb-fortune", Action::RefreshFortune),
+      ("kb-layout", Action::SwitchLayout),
+      ("kb-power", Action::OpenPower),
+    ] {
+      if let Some(value) = config.opt_str(name) {
+        let chord = match value.parse::<u8>() {
+          Ok(n @ 1..=12) => Chord {
+            code: KeyCode::F(n),
+            modifiers: KeyModifiers::NONE,
+          },
+
+          _ => return Err(format!("invalid value '{value}' for {}: must be an F-key between 1 and 12", config.origin(name)).into()),
+        };
+
+        if action == Action::SwitchLayout && !layouts {
+          continue;
+        }
+
+        keymap.bind(action, vec![chord], config.origin(name));
+      }
+    }
+
+    let mut rebound: Vec<(Action, Vec<Chord>)> = Vec::new();
+
+    for spec in config.opt_strs("keybinding") {
+      let invalid = |err: String| format!("invalid value '{spec}' for {}: {err}", config.origin("keybinding"));
+
+      let (action, chords) = spec.split_once('=').ok_or_else(|| invalid("must be ACTION=CHORD[,CHORD...]".to_string()))?;
+      let action = action.trim().parse::<Action>().map_err(invalid)?;
+
+      let chords = match chords.trim() {
+        "none" => vec![],
+        chords => chords.split(',').map(|chord| chord.trim().parse::<Chord>()).collect::<Result<_, _>>().map_err(invalid)?,
+      };
+
+      match rebound.iter_mut().find(|(other, _)| *other == action) {
+        Some((_, existing)) => existing.extend(chords),
+        None => rebound.push((action, chords)),
+      }
+    }
+
+    for (action, chords) in rebound {
+      keymap.bind(action, chords, config.origin("keybinding"));
+    }
+
+    keymap.check()?;
+
+    Ok(keymap)
+  }
+
+  // Replaces the chords bound to an action.
+  fn bind(&mut self, action: Action, chords: Vec<Chord>, origin: String) {
+    self.bindings.retain(|binding| binding.action != action);
+
+    for chord in chords {
+      self.bindings.push(Binding {
+        chord,
+        action,
+        origin: origin.clone(),
+      });
+    }
+  }
+
+  // Makes sure no chord is bound twice in tables that can be active at the same
+  // time, and that prompts can still receive every character.
+  fn check(&self) -> Result<(), String> {
+    for (index, binding) in self.bindings.iter().enumerate() {
+      let table = binding.action.table();
+
+      if table != Table::Menu && binding.chord.is_printable() {
+        return Err(format!(
+          "key chord '{}' bound to {} from {} would prevent typing it in prompts",
+          binding.chord,
+          binding.action.name(),
+          binding.origin
+        ));
+      }
+
+      let conflict = self.bindings[index + 1..].iter().find(|other| {
+        let other_table = other.action.table();
+
+        other.chord == binding.chord && (table == other_table || table == Table::Global || other_table == Table::Global)
+      });
+
+      if let Some(other) = conflict {
+        return Err(format!(
+          "key chord '{}' is bound to both {} from {} and {} from {}",
+          binding.chord,
+          binding.action.name(),
+          binding.origin,
+          other.action.name(),
+          other.origin
+        ));
+      }
+    }
+
+    Ok(())
+  }
+
+  // Finds the action bound to a key in the given mode.
+  pub fn action(&self, mode: Mode, key: &KeyEvent) -> Option<Action> {
+    let table = Table::of(mode);
+
+    self
+      .bindings
+      .iter()
+      .filter(|binding| binding.action.table() == table || binding.action.table() == Table::Global)
+      .find(|binding| binding.chord.matches(key))
+      .map(|binding| binding.action)
+  }
+
+  // Returns the label of the first chord bound to an action, to be displayed in
+  // hints.
+  pub fn hint(&self, action: Action) -> Option<String> {
+    self.bindings.iter().find(|binding| binding.action == action).map(|binding| binding.chord.to_string())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+  use super::{Action, Chord, Keymap};
+  use crate::{config::Config, Mode};
+
+  fn keymap(args: &[&str], file: &str) -> Result<Keymap, String> {
+    let config = Config::parse(args, file).map_err(|err| err.to_string())?;
+
+    Keymap::from_config(&config, false).map_err(|err| err.to_string())
+  }
+
+  fn error(args: &[&str], file: &str) -> String {
+    Config::rejection(args, file, |config| Keymap::from_config(config, false))
+  }
+
+  #[test]
+  fn chords_round_trip() {
+    let cases = [
+      ("ctrl+p", "^P"),
+      ("Ctrl+P", "^P"),
+      ("alt+s", "M-s"),
+      ("ctrl+alt+x", "^M-X"),
+      ("shift+tab", "S-Tab"),
+      ("ctrl+left", "^Left"),
+      ("F5", "F5"),
+      ("f12", "F12"),
+      ("alt+F1", "M-F1"),
+      ("pageup", "Pageup"),
+      ("space", "Space"),
+      ("k", "k"),
+      ("K", "K"),
+      ("+", "+"),
+      ("alt++", "M-+"),
+      ("^", "^"),
+    ];
+
+    for (spec, shown) in cases {
+      let chord: Chord = spec.parse().unwrap();
+
+      assert_eq!(chord.to_string(), shown, "{}", spec);
+      assert_eq!(shown.parse::<Chord>(), Ok(chord), "{}", shown);
+    }
+  }
+
+  #[test]
+  fn invalid_chords() {
+    for spec in ["", "ctrl+", "F0", "F13", "hyper+x", "shift+a", "S-a", "nope", "M-"] {
+      assert!(spec.parse::<Chord>().is_err(), "{}", spec);
+    }
+  }
+
+  #[test]
+  fn default_keymap() {
+    let keymap = keymap(&[], "").unwrap();
+    let key = |code, modifiers| KeyEvent::new(code, modifiers);
+
+    assert_eq!(keymap.hint(Action::OpenPower).as_deref(), Some("F12"));
+    assert_eq!(keymap.hint(Action::SwitchLayout), None);
+    assert_eq!(
+      keymap.action(Mode::Username, &key(KeyCode::Char('A'), KeyModifiers::CONTROL)),
+      Some(Action::LineStart)
+    );
+    assert_eq!(keymap.action(Mode::Username, &key(KeyCode::Char('k'), KeyModifiers::NONE)), None);
+    assert_eq!(keymap.action(Mode::Sessions, &key(KeyCode::Char('k'), KeyModifiers::NONE)), Some(Action::MenuUp));
+    assert_eq!(keymap.action(Mode::Sessions, &key(KeyCode::F(12), KeyModifiers::NONE)), Some(Action::OpenPower));
+  }
+
+  #[test]
+  fn rebinding() {
+    let keymap = keymap(&["--keybinding", "open-power=ctrl+q,F11", "--keybinding", "menu-up=g"], "").unwrap();
+    let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+
+    assert_eq!(keymap.hint(Action::OpenPower).as_deref(), Some("^Q"));
+    assert_eq!(keymap.action(Mode::Password, &key(KeyCode::F(11))), Some(Action::OpenPower));
+    assert_eq!(keymap.action(Mode::Password, &key(KeyCode::F(12))), None);
+    assert_eq!(keymap.action(Mode::Users, &key(KeyCode::Char('g'))), Some(Action::MenuUp));
+    assert_eq!(keymap.action(Mode::Users, &key(KeyCode::Char('k'))), None);
+  }
+
+  #[test]
+  fn unbinding() {
+    let keymap = keymap(&["--keybinding", "open-power=none"], "").unwrap();
+
+    assert_eq!(keymap.hint(Action::OpenPower), None);
+    assert_eq!(keymap.action(Mode::Username, &KeyEvent::new(KeyCode::F(12), KeyModifiers::NONE)), None);
+  }
+
+  #[test]
+  fn rejects_printable_chords_in_prompts() {
+    assert_eq!(
+      error(&["--keybinding", "line-start=y"], ""),
+      "key chord 'y' bound to line-start from --keybinding would prevent typing it in prompts"
+    );
+    assert_eq!(
+      error(&["--keybinding", "open-command=space"], ""),
+      "key chord 'Space' bound to open-command from --keybinding would prevent typing it in prompts"
+    );
+  }
+
+  #[test]
+  fn rejects_duplicate_chords() {
+    // Global and prompt bindings overlap.
+    assert_eq!(
+      error(&["--keybinding", "open-power=ctrl+a"], ""),
+      "key chord '^A' is bound to both line-start from default and open-power from --keybinding"
+    );
+
+    // So do bindings of the same table.
+    assert_eq!(
+      error(&["--keybinding", "menu-up=j"], ""),
+      "key chord 'j' is bound to both menu-down from default and menu-up from --keybinding"
+    );
+
+    assert_eq!(
+      error(&["--kb-power", "2"], ""),
+      "key chord 'F2' is bound to both open-command from default and open-power from --kb-power"
+    );
+
+    // Prompt and menu bindings are never active together.
+    assert!(keymap(&["--keybinding", "menu-up=ctrl+a"], "").is_ok());
+  }
+
+  #[test]
+  fn rejects_invalid_values() {
+    assert_eq!(
+      error(&["--kb-power", "13"], ""),
+      "invalid value '13' for --kb-power: must be an F-key between 1 and 12"
+    );
+    assert!(error(&[], "[keybindings]\npower = 0\n").ends_with("'keybindings.power': must be an F-key between 1 and 12"));
+    assert_eq!(
+      error(&["--keybinding", "power=F1"], ""),
+      "invalid value 'power=F1' for --keybinding: unknown action 'power'"
+    );
+    assert_eq!(
+      error(&["--keybinding", "open-power"], ""),
+      "invalid value 'open-power' for --keybinding: must be ACTION=CHORD[,CHORD...]"
+    );
+  }
+}
@@ -0,0 +1,53 @@
+use std::{error::Error, fs, time::Duration};
+
+use tokio::process::Command;
+
+use crate::helpers;
+
+const VCONSOLE_CONF: &str = "/etc/vconsole.conf";
+const LOADKEYS_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Reads the keymap the console was configured with at boot, if it can be
+// found.
+pub fn get_console_layout() -> Option<String> {
+  let conf = fs::read_to_string(VCONSOLE_CONF).ok()?;
+
+  conf.lines().find_map(|line| {
+    let value = line.trim().strip_prefix("KEYMAP=")?.trim_matches(|c| c == '"' || c == '\'');
+
+    match value.is_empty() {
+      true => None,
+      false => Some(value.to_string()),
+    }
+  })
+}
+
+// Returns the layout following the current one in the configured list,
+// wrapping around at the end. If the current layout is not part of the list,
+// the first one is returned.
+pub fn next_layout<'a>(layouts: &'a [String], current: Option<&str>) -> Option<&'a String> {
+  let next = match current.and_then(|current| layouts.iter().position(|layout| layout == current)) {
+    Some(index) => (index + 1) % layouts.len(),
+    None => 0,
+  };
+
+  layouts.get(next)
+}
+
+// Loads a keymap into the console with `loadkeys`.
+pub async fn load_layout(layout: &str) -> Result<(), Box<dyn Error>> {
+  let mut command = Command::new("loadkeys");
+  command.args(["-q", layout]);
+
+  match helpers::output(command, LOADKEYS_TIMEOUT).await? {
+    output if output.status.success() => Ok(()),
+    output => {
+      let stderr = String::from_utf8_lossy(&output.stderr);
+
+      match stderr.trim() {
+        "" => Err(format!("loadkeys exited with {}", output.status).into()),
+        stderr => Err(stderr.into()),
+      }
+    }
+  }
+}
@@ -4,13 +4,25 @@ extern crate smart_default;
 #[macro_use]
 mod macros;
 
+mod check;
+mod completion;
+mod config;
+mod console;
+mod desktop;
 mod event;
 mod fortune;
 mod greeter;
+mod helpers;
+mod history;
 mod info;
 mod ipc;
+mod issue;
 mod keyboard;
+mod keymap;
+mod layout;
+mod mouse;
 mod power;
+mod settings;
 mod ui;
 
 use std::{
@@ -21,9 +33,11 @@ use std::{
 };
 
 use crossterm::{
+  event::{DisableMouseCapture, EnableMouseCapture},
   execute,
   terminal::{disable_raw_mode, LeaveAlternateScreen},
 };
+use console::LockSource;
 use event::Event;
 use greetd_ipc::Request;
 use nix::{
@@ -37,8 +51,6 @@ use tui::{backend::CrosstermBackend, Terminal};
 
 use crossterm::terminal::{enable_raw_mode, EnterAlternateScreen};
 
-use crate::fortune::get_fortune;
-
 pub use self::greeter::*;
 use self::{event::Events, ipc::Ipc};
 
@@ -68,36 +80,54 @@ where
   enable_raw_mode()?;
   execute!(io::stdout(), EnterAlternateScreen)?;
 
+  if greeter.settings.mouse {
+    execute!(io::stdout(), EnableMouseCapture)?;
+  }
+
+  let locks = LockSource::detect();
+
+  events.listen(locks);
+
+  if locks == LockSource::Console {
+    helpers::watch_locks(events.sender());
+  }
+
   let mut terminal = Terminal::new(backend)?;
 
   terminal.clear()?;
 
-  greeter.fortune = get_fortune();
+  helpers::refresh_fortune(&greeter);
+  helpers::refresh_widget_command(&greeter);
+  helpers::watch_system(&greeter);
   greeter.clear_request = false;
 
   let ipc = Ipc::new();
 
-  if greeter.remember && !greeter.username.value.is_empty() {
+  if greeter.settings.remember && !greeter.username.value.is_empty() {
     greeter.working = true;
 
     tracing::info!("creating remembered session for user {}", greeter.username.value);
 
     ipc
       .send(Request::CreateSession {
-        username: greeter.username.value.clone(),
+        username: greeter.username.value.to_string(),
       })
       .await;
   }
 
   let greeter = Arc::new(RwLock::new(greeter));
 
+  helpers::watch_clock(greeter.clone(), events.sender());
+
   tokio::task::spawn({
     let greeter = greeter.clone();
     let mut ipc = ipc.clone();
+    let sender = events.sender();
 
     async move {
       loop {
         let _ = ipc.handle(greeter.clone()).await;
+        let _ = sender.send(Event::Render).await;
       }
     }
   });
@@ -114,9 +144,18 @@ where
       return Err(status.into());
     }
 
+    // Any event may change what is displayed, so the screen is redrawn after
+    // each one, but only once a burst of events was handled. Nothing is drawn
+    // while no events come in.
+    if !events.pending() {
+      ui::draw(greeter.clone(), &mut terminal).await?;
+    }
+
     match events.next().await {
-      Some(Event::Render) => ui::draw(greeter.clone(), &mut terminal).await?,
+      Some(Event::Render) => {}
       Some(Event::Key(key)) => keyboard::handle(greeter.clone(), key, ipc.clone()).await?,
+      Some(Event::Mouse(event)) => mouse::handle(greeter.clone(), event, ipc.clone()).await?,
+      Some(Event::Helper(update)) => greeter.write().await.update(update),
 
       Some(Event::Exit(status)) => {
         crate::exit(&mut *greeter.write().await, status).await;
@@ -124,8 +163,9 @@ where
 
       Some(Event::PowerCommand(command)) => {
         if let PowerPostAction::ClearScreen = power::run(&greeter, command).await {
-          execute!(io::stdout(), LeaveAlternateScreen)?;
-          terminal.set_cursor(1, 1)?;
+          console::restore();
+          execute!(io::stdout(), DisableMouseCapture, LeaveAlternateScreen)?;
+          terminal.set_cursor_position((1, 1))?;
           terminal.clear()?;
           disable_raw_mode()?;
 
@@ -171,8 +211,9 @@ async fn exit(greeter: &mut Greeter, status: AuthStatus) {
   }
 
   clear_screen();
+  console::restore();
 
-  let _ = execute!(io::stdout(), LeaveAlternateScreen);
+  let _ = execute!(io::stdout(), DisableMouseCapture, LeaveAlternateScreen);
   let _ = disable_raw_mode();
 
   greeter.exit = Some(status);
@@ -183,8 +224,9 @@ fn register_panic_handler() {
 
   std::panic::set_hook(Box::new(move |info| {
     clear_screen();
+    console::restore();
 
-    let _ = execute!(io::stdout(), LeaveAlternateScreen);
+    let _ = execute!(io::stdout(), DisableMouseCapture, LeaveAlternateScreen);
     let _ = disable_raw_mode();
 
     hook(info);
@@ -206,7 +248,7 @@ fn init_logger(greeter: &Greeter) -> Option<WorkerGuard> {
 
   let logfile = OpenOptions::new().write(true).create(true).append(true).clone();
 
-  match (greeter.debug, logfile.open(&greeter.logfile)) {
+  match (greeter.settings.debug, logfile.open(&greeter.settings.logfile)) {
     (true, Ok(file)) => {
       let (appender, guard) = tracing_appender::non_blocking(file);
       let target = Targets::new().with_target("tuigreet", LevelFilter::DEBUG);
@@ -0,0 +1,89 @@
+use std::{error::Error, mem, sync::Arc};
+
+use crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
+use tokio::sync::RwLock;
+use tui::layout::Position;
+
+use crate::{
+  ipc::Ipc,
+  keyboard::run_action,
+  keymap::Action,
+  ui::common::menu::MenuControl,
+  Greeter, Mode,
+};
+
+// Act on mouse events, when mouse capture is enabled.
+//
+// Clicks are matched against the parts of the screen recorded during the last
+// draw: status bar buttons trigger their action, menu entries are selected and
+// then validated with a second click, and the username line can be clicked to
+// go back to it.
+pub async fn handle(greeter: Arc<RwLock<Greeter>>, input: MouseEvent, ipc: Ipc) -> Result<(), Box<dyn Error>> {
+  let mut greeter = greeter.write().await;
+
+  if greeter.working {
+    return Ok(());
+  }
+
+  let position = Position::new(input.column, input.row);
+
+  match input.kind {
+    MouseEventKind::ScrollUp => with_menu(&mut greeter, |menu| menu.scroll_up()),
+    MouseEventKind::ScrollDown => with_menu(&mut greeter, |menu| menu.scroll_down()),
+    MouseEventKind::Down(MouseButton::Left) => click(&mut greeter, position, &ipc).await,
+
+    _ => {}
+  }
+
+  Ok(())
+}
+
+async fn click(greeter: &mut Greeter, position: Position, ipc: &Ipc) {
+  let button = greeter.regions.buttons.iter().find(|(rect, _)| rect.contains(position));
+
+  if let Some((_, action)) = button {
+    run_action(greeter, *action, ipc).await;
+
+    return;
+  }
+
+  if let Some(menu) = greeter.menu() {
+    match menu.option_at(position) {
+      Some(index) if menu.is_selected(index) => run_action(greeter, Action::Validate, ipc).await,
+      Some(index) => menu.select(index),
+      None => {}
+    }
+
+    return;
+  }
+
+  let on_username = greeter.regions.username.is_some_and(|rect| rect.contains(position));
+
+  match greeter.mode {
+    // Going back to the username cancels the session being authenticated,
+    // but keeps what was typed.
+    Mode::Password if on_username => {
+      let username = mem::take(&mut greeter.username);
+
+      Ipc::cancel(greeter).await;
+      greeter.reset(false).await;
+      greeter.username = username;
+    }
+
+    // Without a username, the user menu is opened, the same way as with Enter.
+    Mode::Username if on_username && greeter.username.value.is_empty() => {
+      run_action(greeter, Action::Validate, ipc).await;
+    }
+
+    _ => {}
+  }
+}
+
+fn with_menu<F>(greeter: &mut Greeter, action: F)
+where
+  F: FnOnce(&mut dyn MenuControl),
+{
+  if let Some(menu) = greeter.menu() {
+    action(menu);
+  }
+}
@@ -18,7 +18,7 @@ pub async fn power(greeter: &mut Greeter, option: PowerOption) {
     Some(Power {
       command: Some(args), ..
     }) => {
-      let command = match greeter.power_setsid {
+      let command = match greeter.settings.power_setsid {
         true => {
           let mut command = Command::new("setsid");
           command.args(args.split(' '));
@@ -0,0 +1,484 @@
+use std::{env, error::Error, fmt::Display, path::PathBuf, str::FromStr, time::Duration};
+
+use crate::{
+  config::Config,
+  fortune::FortuneSource,
+  info::get_min_max_uids,
+  keymap::Keymap,
+  ui::{
+    common::{
+      color::ColorDepth,
+      font::{Font, FONTS},
+      style::Theme,
+    },
+    sessions::SessionType,
+    widgets::{Widget, WidgetLayout},
+  },
+  SecretDisplay,
+};
+
+const DEFAULT_LOG_FILE: &str = "/tmp/tuigreet.log";
+const DEFAULT_ASTERISKS_CHARS: &str = "*";
+const DEFAULT_CLOCK_FONT: &str = "block";
+const DEFAULT_FORTUNE_TIMEOUT: u64 = 1000;
+// `startx` wants an absolute path to the executable as a first argument.
+// We don't want to resolve the session command in the greeter though, so it should be additionally wrapped with a known noop command (like `/usr/bin/env`).
+pub const DEFAULT_XSESSION_WRAPPER: &str = "startx /usr/bin/env";
+
+// Validated, typed settings, resolved once at startup from the command line
+// and the configuration file.
+#[derive(SmartDefault)]
+pub struct Settings {
+  // Only validate the configuration, instead of running the greeter.
+  pub check_config: bool,
+  // Configuration file the settings were read from, if any.
+  pub config_file: Option<PathBuf>,
+
+  pub debug: bool,
+  #[default(DEFAULT_LOG_FILE.to_string())]
+  pub logfile: String,
+
+  // Style object for the terminal UI
+  pub theme: Theme,
+  // Colors the terminal can display, which the theme was downgraded to.
+  #[default(ColorDepth::TrueColor)]
+  pub color_depth: ColorDepth,
+  // How should secrets be displayed?
+  pub secret_display: SecretDisplay,
+
+  // Width of the main window where content is displayed.
+  #[default(80)]
+  pub width: u16,
+  // Padding of the screen.
+  pub window_padding: u16,
+  // Padding of the main window where content is displayed, including its
+  // border.
+  #[default(2)]
+  pub container_padding: u16,
+  // Spacing between each prompt.
+  #[default(1)]
+  pub prompt_padding: u16,
+
+  // Whether user menu is enabled.
+  pub user_menu: bool,
+  // Range of UIDs to display in the user menu.
+  pub user_menu_uids: (u16, u16),
+
+  // Whether last logged-in user should be remembered.
+  pub remember: bool,
+  // Whether last launched session (regardless of user) should be remembered.
+  pub remember_session: bool,
+  // Whether last launched session for the current user should be remembered.
+  pub remember_user_session: bool,
+
+  // Default command, and its environment, provided with `--cmd` and `--env`.
+  pub command: Option<(String, Option<Vec<String>>)>,
+  // List of session files found on disk.
+  pub session_paths: Vec<(PathBuf, SessionType)>,
+  // Wrapper command to prepend to non-X11 sessions.
+  pub session_wrapper: Option<String>,
+  // Wrapper command to prepend to X11 sessions.
+  pub xsession_wrapper: Option<String>,
+
+  // Whether the issue file should be shown.
+  pub issue: bool,
+  // Whether the date and the clock should be shown.
+  pub time: bool,
+  // Static text shown by the greeting widget.
+  pub greeting: Option<String>,
+
+  // Font used to draw the clock, or `None` to draw it as regular text.
+  #[default(Font::find(DEFAULT_CLOCK_FONT))]
+  pub clock_font: Option<&'static Font>,
+  // strftime format of the clock.
+  #[default("%H:%M".to_string())]
+  pub clock_format: String,
+  // strftime format of the date, instead of the one from the locale.
+  pub date_format: Option<String>,
+
+  // Source of the text displayed under the prompt.
+  pub fortune: Option<FortuneSource>,
+  // Maximum time allowed for a fortune command to run.
+  #[default(Duration::from_millis(DEFAULT_FORTUNE_TIMEOUT))]
+  pub fortune_timeout: Duration,
+
+  // Widgets displayed in each region of the screen.
+  pub widgets: WidgetLayout,
+  // Command whose output is displayed by the command widget.
+  pub widget_command: Option<String>,
+
+  // Console keymaps the user can cycle through.
+  pub keyboard_layouts: Vec<String>,
+  // Whether mouse events are captured.
+  pub mouse: bool,
+
+  // Custom commands for power options.
+  pub power_shutdown: Option<String>,
+  pub power_reboot: Option<String>,
+  // Whether to prefix the power commands with `setsid`.
+  #[default(true)]
+  pub power_setsid: bool,
+
+  // Key chords bound to each action.
+  pub keymap: Keymap,
+}
+
+impl Settings {
+  pub fn from_config(config: &Config) -> Result<Settings, Box<dyn Error>> {
+    let mut settings = Settings {
+      check_config: config.opt_present("check-config"),
+      config_file: config.path().map(PathBuf::from),
+      ..Default::default()
+    };
+
+    if config.opt_present("debug") {
+      settings.debug = true;
+
+      if let Some(file) = config.opt_str("debug") {
+        settings.logfile = file;
+      }
+    }
+
+    if let Some(spec) = config.opt_str("theme") {
+      settings.theme = Theme::load(&spec)
+        .map_err(|err| format!("invalid value '{spec}' for {}: {err}", config.origin("theme")))?;
+
+      if !settings.theme.problems().is_empty() {
+        let problems = settings.theme.problems().join("; ");
+
+        return Err(format!("invalid value '{spec}' for {}: {problems}", config.origin("theme")).into());
+      }
+    }
+
+    settings.color_depth = match config.opt_str("color-depth") {
+      None => ColorDepth::detect(),
+      Some(value) => ColorDepth::parse(&value).ok_or_else(|| {
+        format!(
+          "invalid value '{value}' for {}: must be one of auto, monochrome, 16, 256 or truecolor",
+          config.origin("color-depth")
+        )
+      })?,
+    };
+
+    settings.theme.downgrade(settings.color_depth);
+
+    if config.opt_present("asterisks") {
+      let asterisk = match config.opt_str("asterisks-char") {
+        Some(value) if value.chars().count() < 1 => {
+          return Err(
+            format!(
+              "{} must have at least one character as its value",
+              config.origin("asterisks-char")
+            )
+            .into(),
+          );
+        }
+
+        Some(value) => value,
+        None => DEFAULT_ASTERISKS_CHARS.to_string(),
+      };
+
+      settings.secret_display = SecretDisplay::Character(asterisk);
+    }
+
+    settings.width = parse(config, "width")?.unwrap_or(settings.width);
+    settings.window_padding = parse(config, "window-padding")?.unwrap_or(settings.window_padding);
+    settings.container_padding = match parse::<u16>(config, "container-padding")? {
+      Some(padding) => padding.checked_add(1).ok_or_else(|| {
+        format!(
+          "invalid value '{padding}' for {}: must be less than {}",
+          config.origin("container-padding"),
+          u16::MAX
+        )
+      })?,
+
+      None => settings.container_padding,
+    };
+    settings.prompt_padding = parse(config, "prompt-padding")?.unwrap_or(settings.prompt_padding);
+
+    if config.opt_present("user-menu") {
+      settings.user_menu = true;
+
+      let (min_uid, max_uid) = get_min_max_uids(
+        parse(config, "user-menu-min-uid")?,
+        parse(config, "user-menu-max-uid")?,
+      );
+
+      if min_uid >= max_uid {
+        return Err(
+          format!(
+            "minimum UID ({min_uid}) from {} must be less than maximum UID ({max_uid}) from {}",
+            config.origin("user-menu-min-uid"),
+            config.origin("user-menu-max-uid")
+          )
+          .into(),
+        );
+      }
+
+      settings.user_menu_uids = (min_uid, max_uid);
+    }
+
+    if config.opt_present("remember-session") && config.opt_present("remember-user-session") {
+      return Err(
+        format!(
+          "only one of {} and {} may be used at the same time",
+          config.origin("remember-session"),
+          config.origin("remember-user-session")
+        )
+        .into(),
+      );
+    }
+    if config.opt_present("remember-user-session") && !config.opt_present("remember") {
+      return Err(
+        format!(
+          "{} must be used with {}",
+          config.origin("remember-user-session"),
+          config.origin("remember")
+        )
+        .into(),
+      );
+    }
+
+    settings.remember = config.opt_present("remember");
+    settings.remember_session = config.opt_present("remember-session");
+    settings.remember_user_session = config.opt_present("remember-user-session");
+
+    if let Some(command) = config.opt_str("cmd") {
+      let envs = match config.opt_present("env") {
+        true => Some(config.opt_strs("env")),
+        false => None,
+      };
+
+      for env in envs.iter().flatten() {
+        if !env.contains('=') {
+          return Err(
+            format!(
+              "malformed environment variable definition for '{env}' in {}",
+              config.origin("env")
+            )
+            .into(),
+          );
+        }
+      }
+
+      settings.command = Some((command, envs));
+    }
+
+    if let Some(dirs) = config.opt_str("sessions") {
+      settings
+        .session_paths
+        .extend(env::split_paths(&dirs).map(|dir| (dir, SessionType::Wayland)));
+    }
+
+    if let Some(dirs) = config.opt_str("xsessions") {
+      settings
+        .session_paths
+        .extend(env::split_paths(&dirs).map(|dir| (dir, SessionType::X11)));
+    }
+
+    settings.session_wrapper = config.opt_str("session-wrapper");
+
+    if !config.opt_present("no-xsession-wrapper") {
+      settings.xsession_wrapper = config
+        .opt_str("xsession-wrapper")
+        .or_else(|| Some(DEFAULT_XSESSION_WRAPPER.to_string()));
+    }
+
+    settings.issue = config.opt_present("issue");
+    settings.time = config.opt_present("time");
+    settings.greeting = config.opt_str("greeting").filter(|greeting| !greeting.trim().is_empty());
+    settings.mouse = config.opt_present("mouse");
+
+    if let Some(name) = config.opt_str("clock-font") {
+      settings.clock_font = match name.as_str() {
+        "plain" => None,
+
+        name => match Font::find(name) {
+          Some(font) => Some(font),
+          None => {
+            let fonts = FONTS.iter().map(|font| font.name).collect::<Vec<_>>().join(", ");
+
+            return Err(format!("invalid value '{name}' for {}: must be one of {fonts} or plain", config.origin("clock-font")).into());
+          }
+        },
+      };
+    }
+
+    settings.clock_format = match config.opt_str("clock-format") {
+      Some(format) => format,
+
+      None => {
+        let hours = if config.opt_present("clock-12h") { "%I" } else { "%H" };
+        let seconds = if config.opt_present("clock-seconds") { ":%S" } else { "" };
+        let period = if config.opt_present("clock-12h") { " %p" } else { "" };
+
+        format!("{hours}:%M{seconds}{period}")
+      }
+    };
+
+    settings.date_format = config.opt_str("date-format");
+
+    settings.fortune = match config.opt_str("fortune") {
+      Some(spec) => match FortuneSource::parse(&spec) {
+        Ok(source) => Some(source),
+        Err(err) => return Err(format!("invalid value '{spec}' for {}: {err}", config.origin("fortune")).into()),
+      },
+
+      None => FortuneSource::system(),
+    };
+
+    if let Some(timeout) = parse(config, "fortune-timeout")? {
+      settings.fortune_timeout = Duration::from_millis(timeout);
+    }
+
+    for spec in config.opt_strs("layout") {
+      settings
+        .widgets
+        .set(&spec)
+        .map_err(|err| format!("invalid value '{spec}' for {}: {err}", config.origin("layout")))?;
+    }
+
+    settings.widget_command = config.opt_str("widget-command");
+
+    if settings.widgets.contains(Widget::Command) && settings.widget_command.is_none() {
+      return Err(
+        format!(
+          "the command widget from {} requires {}",
+          config.origin("layout"),
+          config.origin("widget-command")
+        )
+        .into(),
+      );
+    }
+
+    for layouts in config.opt_strs("keyboard-layouts") {
+      for layout in layouts.split(',').map(str::trim) {
+        if layout.is_empty() || layout.contains(char::is_whitespace) {
+          return Err(format!("invalid keyboard layout '{layout}' in {}", config.origin("keyboard-layouts")).into());
+        }
+
+        settings.keyboard_layouts.push(layout.to_string());
+      }
+    }
+
+    settings.power_shutdown = config.opt_str("power-shutdown");
+    settings.power_reboot = config.opt_str("power-reboot");
+    settings.power_setsid = !config.opt_present("power-no-setsid");
+
+    settings.keymap = Keymap::from_config(config, !settings.keyboard_layouts.is_empty())?;
+
+    Ok(settings)
+  }
+}
+
+// Parses the value of an option into the requested type, failing with a
+// message naming where the value came from if it is invalid.
+fn parse<T>(config: &Config, name: &str) -> Result<Option<T>, Box<dyn Error>>
+where
+  T: FromStr,
+  T::Err: Display,
+{
+  match config.opt_str(name) {
+    None => Ok(None),
+    Some(value) => match value.parse::<T>() {
+      Ok(value) => Ok(Some(value)),
+      Err(err) => Err(format!("invalid value '{value}' for {}: {err}", config.origin(name)).into()),
+    },
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::error::Error;
+
+  use super::Settings;
+  use crate::config::Config;
+
+  // Reads settings from command line options and the contents of a
+  // configuration file.
+  fn settings(args: &[&str], file: &str) -> Result<Settings, Box<dyn Error>> {
+    Settings::from_config(&Config::parse(args, file)?)
+  }
+
+  fn error(args: &[&str], file: &str) -> String {
+    Config::rejection(args, file, Settings::from_config)
+  }
+
+  #[test]
+  fn rejects_invalid_themes() {
+    assert_eq!(
+      error(&["--theme", "prompt=blinking;foo=red"], ""),
+      "invalid value 'prompt=blinking;foo=red' for --theme: invalid value 'blinking' for key 'prompt': \
+       unknown color or modifier 'blinking'; unknown key 'foo'"
+    );
+
+    assert!(error(&[], "theme = \"title=double\"").contains("key 'theme'"));
+    assert!(settings(&["--theme", "prompt=green,bold"], "").is_ok());
+  }
+
+  #[test]
+  fn rejects_invalid_types() {
+    let cases = [
+      ("issue = \"yes\"", "key 'issue' must be a boolean, found string"),
+      ("[power]\nsetsid = 1", "key 'power.setsid' must be a boolean, found integer"),
+      ("debug = 3", "key 'debug' must be a boolean or a string, found integer"),
+      ("width = true", "key 'width' must be a string or an integer, found boolean"),
+      ("env = \"FOO=bar\"", "key 'env' must be an array of strings, found string"),
+      ("env = [\"FOO=bar\", 1]", "key 'env' must only contain strings, found integer"),
+      ("[sessions]\nwayland = false", "key 'sessions.wayland' must be a string or an array of strings, found boolean"),
+      ("layout = \"top=none\"", "key 'layout' must be a table, found string"),
+      ("[keymap]\nline-start = 1", "key 'keymap.line-start' must be a string or an array of strings, found integer"),
+      ("[clock]\nsize = 3", "unknown key 'clock.size'"),
+    ];
+
+    for (file, expected) in cases {
+      let err = error(&[], file);
+
+      assert!(err.ends_with(expected), "{}", err);
+    }
+  }
+
+  #[test]
+  fn rejects_invalid_values() {
+    assert_eq!(
+      error(&["--width", "wide"], ""),
+      "invalid value 'wide' for --width: invalid digit found in string"
+    );
+    assert!(error(&[], "width = -1").ends_with("key 'width': invalid digit found in string"));
+    assert_eq!(
+      error(&["--container-padding", "65535"], ""),
+      "invalid value '65535' for --container-padding: must be less than 65535"
+    );
+    assert!(error(&["--debug", "--color-depth", "8"], "").starts_with("invalid value '8' for --color-depth"));
+    assert_eq!(
+      error(&["--cmd", "sway", "--env", "FOO"], ""),
+      "malformed environment variable definition for 'FOO' in --env"
+    );
+    assert!(error(&[], "env = [\"FOO\"]\ncmd = \"sway\"").ends_with("key 'env'"));
+    assert!(error(&["--layout", "middle=clock"], "").starts_with("invalid value 'middle=clock' for --layout: unknown"));
+    assert!(error(&[], "remember.user-session = true").ends_with("must be used with --remember"));
+  }
+
+  #[test]
+  fn rejects_inconsistent_options() {
+    let err = error(&[], "[layout]\nbottom = \"command\"");
+
+    assert!(err.starts_with("the command widget from "), "{}", err);
+    assert!(err.ends_with("key 'layout' requires --widget-command"), "{}", err);
+    assert!(settings(&["--layout", "bottom=command", "--widget-command", "date"], "").is_ok());
+
+    assert_eq!(
+      error(&["--user-menu", "--user-menu-min-uid", "2000", "--user-menu-max-uid", "1000"], ""),
+      "minimum UID (2000) from --user-menu-min-uid must be less than maximum UID (1000) from --user-menu-max-uid"
+    );
+  }
+
+  #[test]
+  fn valid_settings() {
+    let settings = settings(&["--container-padding", "2"], "width = 60\nenv = [\"FOO=bar\"]\ncmd = \"sway\"").unwrap();
+
+    assert_eq!(settings.container_padding, 3);
+    assert_eq!(settings.width, 60);
+    assert_eq!(settings.command, Some(("sway".to_string(), Some(vec!["FOO=bar".to_string()]))));
+  }
+}
@@ -0,0 +1,102 @@
+use std::time::Duration;
+
+use chrono::{Local, Locale};
+
+use crate::{issue::Issue, settings::Settings};
+
+// Large clock displayed above the prompt.
+//
+// Rendering is cached, and only done again when the displayed time changes,
+// which is once a minute unless seconds are shown.
+#[derive(Default)]
+pub struct Clock {
+  cache: Option<(String, Vec<String>)>,
+}
+
+impl Clock {
+  pub fn get(&mut self, settings: &Settings, locale: Locale) -> &[String] {
+    let time = Local::now().format_localized(&settings.clock_format, locale).to_string();
+
+    if !matches!(self.cache, Some((ref cached, _)) if *cached == time) {
+      let rows = match settings.clock_font {
+        Some(font) => font.render(&time),
+        None => vec![time.clone()],
+      };
+
+      self.cache = Some((time, rows));
+    }
+
+    self.cache.as_ref().map(|(_, rows)| rows.as_slice()).unwrap_or_default()
+  }
+}
+
+// Interval at which the displayed time can change, for the clock, the date and
+// the issue to be redrawn on time: every second if either format includes
+// seconds or the issue shows the time, every minute otherwise.
+pub fn tick(settings: &Settings, issue: Option<&Issue>) -> Duration {
+  let formats = [Some(&settings.clock_format), settings.date_format.as_ref()];
+
+  match formats.iter().flatten().any(|format| shows_seconds(format)) || issue.is_some_and(Issue::shows_time) {
+    true => Duration::from_secs(1),
+    false => Duration::from_secs(60),
+  }
+}
+
+fn shows_seconds(format: &str) -> bool {
+  let mut chars = format.chars();
+
+  while let Some(c) = chars.next() {
+    if c != '%' {
+      continue;
+    }
+
+    // Skip padding flags, widths and precisions, such as in `%-S` or `%.3f`.
+    let specifier = chars.find(|c| !matches!(c, '-' | '_' | '0'..='9' | '^' | '#' | '.' | ':'));
+
+    if let Some('S' | 'T' | 'X' | 'r' | 's' | 'c' | '+' | 'f') = specifier {
+      return true;
+    }
+  }
+
+  false
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use super::tick;
+  use crate::settings::Settings;
+
+  #[test]
+  fn ticks_every_second_only_when_seconds_are_shown() {
+    let cases = [
+      ("%H:%M", 60),
+      ("%I:%M %p", 60),
+      ("%a %d %R", 60),
+      ("%H:%M:%S", 1),
+      ("%H:%M:%-S", 1),
+      ("%T", 1),
+      ("%X", 1),
+      ("%s", 1),
+      ("%H:%M:%.3f", 1),
+      ("%H:%M %%S", 60),
+    ];
+
+    for (format, seconds) in cases {
+      let settings = Settings {
+        clock_format: format.to_string(),
+        ..Default::default()
+      };
+
+      assert_eq!(tick(&settings, None), Duration::from_secs(seconds), "{}", format);
+    }
+
+    let settings = Settings {
+      date_format: Some("%A %d %B, %T".to_string()),
+      ..Default::default()
+    };
+
+    assert_eq!(tick(&settings, None), Duration::from_secs(1));
+  }
+}
@@ -2,31 +2,39 @@ use std::error::Error;
 
 use tui::{
   layout::{Constraint, Direction, Layout, Rect},
+  style::Style,
   text::Span,
-  widgets::{Block, BorderType, Borders, Paragraph},
+  widgets::{Block, BorderType, Borders, Clear, Paragraph},
 };
 
 use crate::{ui::util::*, ui::Frame, Greeter};
 
 use super::common::style::Themed;
 
-pub fn draw(greeter: &mut Greeter, f: &mut Frame) -> Result<(u16, u16), Box<dyn Error>> {
-  let theme = &greeter.theme;
+// Maximum number of completion candidates displayed at once.
+const DROPDOWN_ROWS: usize = 8;
 
-  let size = f.size();
-  let (x, y, width, height) = get_rect_bounds(greeter, size, 0);
+pub fn draw(greeter: &mut Greeter, f: &mut Frame, area: Rect) -> Result<(u16, u16), Box<dyn Error>> {
+  let theme = &greeter.settings.theme;
 
-  let container_padding = greeter.container_padding();
+  let (x, y, width, height) = get_rect_bounds(greeter, area, 0);
+
+  let container_padding = greeter.settings.container_padding;
 
   let container = Rect::new(x, y, width, height);
-  let frame = Rect::new(x + container_padding, y + container_padding, width - container_padding, height - container_padding);
+  let frame = Rect::new(
+    x + container_padding,
+    y + container_padding,
+    width.saturating_sub(container_padding),
+    height.saturating_sub(container_padding),
+  );
 
   let block = Block::default()
     .title(titleize(&fl!("title_command")))
     .title_style(theme.of(&[Themed::Title]))
     .style(theme.of(&[Themed::Container]))
     .borders(Borders::ALL)
-    .border_type(BorderType::Plain)
+    .border_type(theme.border_type(Themed::Container, BorderType::Plain))
     .border_style(theme.of(&[Themed::Border]));
 
   f.render_widget(block, container);
@@ -38,13 +46,52 @@ pub fn draw(greeter: &mut Greeter, f: &mut Frame) -> Result<(u16, u16), Box<dyn
   let chunks = Layout::default().direction(Direction::Vertical).constraints(constraints.as_ref()).split(frame);
   let cursor = chunks[0];
 
-  let command_value_text = Span::from(&greeter.buffer);
+  let command_rect = Rect::new(1 + cursor.x, cursor.y, get_input_width(greeter, width, &None), 1);
+  let (command, offset) = greeter.command.view(command_rect.width, |_, grapheme| grapheme.to_string());
+
+  let command_value_text = Span::from(command);
   let command_value = Paragraph::new(command_value_text).style(theme.of(&[Themed::Input]));
 
-  f.render_widget(command_value, Rect::new(1 + chunks[0].x, chunks[0].y, get_input_width(greeter, width, &None), 1));
+  f.render_widget(command_value, command_rect);
+
+  draw_candidates(greeter, f, area, container);
+
+  Ok((1 + command_rect.x + offset, cursor.y + 1))
+}
+
+// Draws the completion candidates in a dropdown under the command prompt,
+// scrolled to keep the selected one visible.
+fn draw_candidates(greeter: &Greeter, f: &mut Frame, area: Rect, container: Rect) {
+  let theme = &greeter.settings.theme;
+  let completion = &greeter.completion;
+
+  let space = area.bottom().saturating_sub(container.bottom());
+  let rows = completion.candidates.len().min(DROPDOWN_ROWS).min(space.saturating_sub(2) as usize);
+
+  if rows == 0 {
+    return;
+  }
+
+  let dropdown = Rect::new(container.x, container.bottom(), container.width, rows as u16 + 2);
+  let offset = completion.selected.map(|selected| (selected + 1).saturating_sub(rows)).unwrap_or(0);
+
+  let block = Block::default()
+    .style(theme.of(&[Themed::Menu]))
+    .borders(Borders::ALL)
+    .border_type(theme.border_type(Themed::Menu, BorderType::Plain))
+    .border_style(theme.of(&[Themed::Border]));
+
+  f.render_widget(Clear, dropdown);
+  f.render_widget(block, dropdown);
+
+  for (row, (index, candidate)) in completion.candidates.iter().enumerate().skip(offset).take(rows).enumerate() {
+    let style = match completion.selected == Some(index) {
+      true => theme.of(&[Themed::Selection]),
+      false => Style::default(),
+    };
 
-  let new_command = greeter.buffer.clone();
-  let offset = get_cursor_offset(greeter, new_command.chars().count());
+    let frame = Rect::new(dropdown.x + 2, dropdown.y + 1 + row as u16, dropdown.width.saturating_sub(4), 1);
 
-  Ok((2 + cursor.x + offset as u16, cursor.y + 1))
+    f.render_widget(Paragraph::new(Span::styled(candidate.label.as_str(), style)), frame);
+  }
 }
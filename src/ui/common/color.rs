@@ -0,0 +1,238 @@
+use std::{
+  env,
+  fmt::{self, Display},
+};
+
+use tui::style::Color;
+
+use crate::console::is_virtual_console;
+
+// Colors the terminal can display, from the least to the most capable.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum ColorDepth {
+  // No colors at all, only bold and reverse video.
+  Monochrome,
+  // The 16 ANSI colors, with only the first 8 usable as background colors, as
+  // on the Linux virtual console.
+  Ansi,
+  // The 256 colors of the xterm palette.
+  Indexed,
+  // Any RGB color.
+  TrueColor,
+}
+
+impl Display for ColorDepth {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      ColorDepth::Monochrome => f.write_str("monochrome"),
+      ColorDepth::Ansi => f.write_str("16 colors"),
+      ColorDepth::Indexed => f.write_str("256 colors"),
+      ColorDepth::TrueColor => f.write_str("true color"),
+    }
+  }
+}
+
+impl ColorDepth {
+  pub fn parse(value: &str) -> Option<ColorDepth> {
+    match value {
+      "auto" => Some(ColorDepth::detect()),
+      "monochrome" => Some(ColorDepth::Monochrome),
+      "16" => Some(ColorDepth::Ansi),
+      "256" => Some(ColorDepth::Indexed),
+      "truecolor" => Some(ColorDepth::TrueColor),
+      _ => None,
+    }
+  }
+
+  // Guesses what the terminal supports from the environment.
+  pub fn detect() -> ColorDepth {
+    let term = env::var("TERM").ok();
+    let colorterm = env::var("COLORTERM").ok();
+
+    ColorDepth::from_env(term.as_deref(), colorterm.as_deref(), is_virtual_console())
+  }
+
+  fn from_env(term: Option<&str>, colorterm: Option<&str>, virtual_console: bool) -> ColorDepth {
+    let term = term.unwrap_or_default();
+
+    // The Linux console silently ignores the color depth it is told it has.
+    if virtual_console || term == "linux" {
+      return ColorDepth::Ansi;
+    }
+
+    if term.is_empty() || term == "dumb" {
+      return ColorDepth::Monochrome;
+    }
+
+    if let Some("truecolor" | "24bit") = colorterm {
+      return ColorDepth::TrueColor;
+    }
+
+    if term.ends_with("-direct") {
+      return ColorDepth::TrueColor;
+    }
+
+    if term.contains("256color") {
+      return ColorDepth::Indexed;
+    }
+
+    ColorDepth::Ansi
+  }
+
+  // Maps a color to the closest one the terminal can display. Background
+  // colors are limited to the first 8 ANSI colors on 16-color terminals.
+  pub fn downgrade(self, color: Color, background: bool) -> Option<Color> {
+    match (self, color) {
+      (_, Color::Reset) | (ColorDepth::TrueColor, _) => Some(color),
+      (ColorDepth::Monochrome, _) => None,
+
+      (ColorDepth::Indexed, Color::Rgb(r, g, b)) => Some(Color::Indexed(nearest(16..=255, (r, g, b)))),
+      (ColorDepth::Indexed, _) => Some(color),
+
+      (ColorDepth::Ansi, _) => {
+        let count = if background { 8 } else { 16 };
+
+        match to_rgb(color) {
+          Some(rgb) => Some(ANSI[nearest(0..=count - 1, rgb) as usize]),
+          None => Some(color),
+        }
+      }
+    }
+  }
+}
+
+// The 16 ANSI colors, by index.
+const ANSI: [Color; 16] = [
+  Color::Black,
+  Color::Red,
+  Color::Green,
+  Color::Yellow,
+  Color::Blue,
+  Color::Magenta,
+  Color::Cyan,
+  Color::Gray,
+  Color::DarkGray,
+  Color::LightRed,
+  Color::LightGreen,
+  Color::LightYellow,
+  Color::LightBlue,
+  Color::LightMagenta,
+  Color::LightCyan,
+  Color::White,
+];
+
+// Usual values of the ANSI colors, as used by xterm.
+const ANSI_RGB: [(u8, u8, u8); 16] = [
+  (0, 0, 0),
+  (205, 0, 0),
+  (0, 205, 0),
+  (205, 205, 0),
+  (0, 0, 238),
+  (205, 0, 205),
+  (0, 205, 205),
+  (229, 229, 229),
+  (127, 127, 127),
+  (255, 0, 0),
+  (0, 255, 0),
+  (255, 255, 0),
+  (92, 92, 255),
+  (255, 0, 255),
+  (0, 255, 255),
+  (255, 255, 255),
+];
+
+// Levels of each component in the 6x6x6 color cube of the xterm palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn to_rgb(color: Color) -> Option<(u8, u8, u8)> {
+  match color {
+    Color::Rgb(r, g, b) => Some((r, g, b)),
+    Color::Indexed(index) => Some(indexed_rgb(index)),
+    color => ANSI.iter().position(|ansi| *ansi == color).map(|index| ANSI_RGB[index]),
+  }
+}
+
+fn indexed_rgb(index: u8) -> (u8, u8, u8) {
+  match index {
+    0..=15 => ANSI_RGB[index as usize],
+
+    16..=231 => {
+      let index = index - 16;
+
+      (
+        CUBE_LEVELS[(index / 36) as usize],
+        CUBE_LEVELS[(index / 6 % 6) as usize],
+        CUBE_LEVELS[(index % 6) as usize],
+      )
+    }
+
+    _ => {
+      let level = 8 + 10 * (index - 232);
+
+      (level, level, level)
+    }
+  }
+}
+
+// Finds the palette index closest to a color, using a weighted distance that
+// roughly follows how the eye perceives differences between colors.
+fn nearest<I>(indices: I, (r, g, b): (u8, u8, u8)) -> u8
+where
+  I: Iterator<Item = u8>,
+{
+  let distance = |(r2, g2, b2): (u8, u8, u8)| {
+    let mean = (r as i32 + r2 as i32) / 2;
+    let (dr, dg, db) = (r as i32 - r2 as i32, g as i32 - g2 as i32, b as i32 - b2 as i32);
+
+    (((512 + mean) * dr * dr) >> 8) + 4 * dg * dg + (((767 - mean) * db * db) >> 8)
+  };
+
+  indices.min_by_key(|index| distance(indexed_rgb(*index))).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+  use tui::style::Color;
+
+  use super::ColorDepth;
+
+  #[test]
+  fn detects_the_color_depth() {
+    let cases = [
+      (Some("linux"), None, false, ColorDepth::Ansi),
+      (Some("xterm-256color"), Some("truecolor"), true, ColorDepth::Ansi),
+      (Some("xterm-256color"), Some("truecolor"), false, ColorDepth::TrueColor),
+      (Some("foot"), Some("24bit"), false, ColorDepth::TrueColor),
+      (Some("xterm-direct"), None, false, ColorDepth::TrueColor),
+      (Some("xterm-256color"), None, false, ColorDepth::Indexed),
+      (Some("screen-256color"), Some("1"), false, ColorDepth::Indexed),
+      (Some("vt220"), None, false, ColorDepth::Ansi),
+      (Some("dumb"), None, false, ColorDepth::Monochrome),
+      (None, None, false, ColorDepth::Monochrome),
+    ];
+
+    for (term, colorterm, virtual_console, depth) in cases {
+      assert_eq!(ColorDepth::from_env(term, colorterm, virtual_console), depth, "{:?}", term);
+    }
+  }
+
+  #[test]
+  fn maps_colors_to_the_closest_supported_one() {
+    let nord = Color::Rgb(0x88, 0xc0, 0xd0);
+
+    assert_eq!(ColorDepth::TrueColor.downgrade(nord, false), Some(nord));
+    assert_eq!(ColorDepth::Indexed.downgrade(nord, false), Some(Color::Indexed(110)));
+    assert_eq!(ColorDepth::Ansi.downgrade(nord, false), Some(Color::Gray));
+    assert_eq!(ColorDepth::Monochrome.downgrade(nord, false), None);
+
+    assert_eq!(ColorDepth::Indexed.downgrade(Color::Indexed(42), false), Some(Color::Indexed(42)));
+    assert_eq!(ColorDepth::Ansi.downgrade(Color::Indexed(196), false), Some(Color::LightRed));
+    assert_eq!(ColorDepth::Ansi.downgrade(Color::Rgb(10, 10, 10), false), Some(Color::Black));
+    assert_eq!(ColorDepth::Ansi.downgrade(Color::Reset, true), Some(Color::Reset));
+
+    // Bright colors cannot be used as backgrounds on the Linux console.
+    assert_eq!(ColorDepth::Ansi.downgrade(Color::LightRed, false), Some(Color::LightRed));
+    assert_eq!(ColorDepth::Ansi.downgrade(Color::LightRed, true), Some(Color::Red));
+    assert_eq!(ColorDepth::Ansi.downgrade(Color::White, true), Some(Color::Gray));
+  }
+}
@@ -0,0 +1,493 @@
+use std::fmt::{self, Display};
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+use zeroize::Zeroize;
+
+// Maximum number of killed texts kept around for yanking.
+const KILL_RING_SIZE: usize = 16;
+
+// Editing operations supported by the line editor.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Edit {
+  Insert(char),
+  Left,
+  Right,
+  WordLeft,
+  WordRight,
+  Start,
+  End,
+  DeleteBackward,
+  DeleteForward,
+  KillWordBackward,
+  KillWordForward,
+  KillToEnd,
+  KillLine,
+  Yank,
+  YankPop,
+}
+
+// Single-line text input, with emacs-style editing.
+//
+// The cursor always sits between two grapheme clusters, so that combining
+// characters and emojis are moved over and deleted as a whole, and the view is
+// computed from the display width of each cluster.
+#[derive(Default)]
+pub struct LineEditor {
+  value: String,
+  // Byte index of the cursor in `value`, always on a grapheme boundary.
+  cursor: usize,
+  // Killed texts, the most recent last.
+  kill_ring: Vec<String>,
+  // Whether the last edit was a kill, in which case the next kill is appended
+  // to the same kill ring entry.
+  killing: bool,
+  // Byte range of the text inserted by the last yank, and the kill ring entry
+  // it came from, so it can be replaced by older entries.
+  yanked: Option<(usize, usize, usize)>,
+  // Number of graphemes scrolled out on the left, when the value is wider
+  // than the field it is displayed in.
+  scroll: usize,
+}
+
+impl From<String> for LineEditor {
+  fn from(value: String) -> LineEditor {
+    LineEditor {
+      cursor: value.len(),
+      value,
+      ..Default::default()
+    }
+  }
+}
+
+impl Display for LineEditor {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.value)
+  }
+}
+
+impl LineEditor {
+  pub fn as_str(&self) -> &str {
+    &self.value
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.value.is_empty()
+  }
+
+  // Byte index of the cursor in the value.
+  pub fn cursor(&self) -> usize {
+    self.cursor
+  }
+
+  // Number of grapheme clusters in the value.
+  pub fn len(&self) -> usize {
+    self.value.graphemes(true).count()
+  }
+
+  // Replaces the value, moving the cursor to its end. The kill ring is kept.
+  pub fn set(&mut self, value: String) {
+    self.value.zeroize();
+    self.value = value;
+    self.cursor = self.value.len();
+    self.killing = false;
+    self.yanked = None;
+    self.scroll = 0;
+  }
+
+  // Replaces the value, moving the cursor to the given byte index.
+  pub fn set_with_cursor(&mut self, value: String, cursor: usize) {
+    self.set(value);
+    self.cursor = cursor.min(self.value.len());
+    self.snap();
+  }
+
+  pub fn clear(&mut self) {
+    self.set(String::new());
+  }
+
+  pub fn apply(&mut self, edit: Edit) {
+    let killing = self.killing;
+    let yanked = self.yanked.take();
+
+    self.killing = false;
+
+    match edit {
+      Edit::Insert(c) => {
+        self.value.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+        self.snap();
+      }
+
+      Edit::Left => self.cursor = self.previous_boundary(self.cursor),
+      Edit::Right => self.cursor = self.next_boundary(self.cursor),
+      Edit::WordLeft => self.cursor = self.previous_word(),
+      Edit::WordRight => self.cursor = self.next_word(),
+      Edit::Start => self.cursor = 0,
+      Edit::End => self.cursor = self.value.len(),
+
+      Edit::DeleteBackward => {
+        let start = self.previous_boundary(self.cursor);
+        self.remove(start, self.cursor);
+      }
+
+      Edit::DeleteForward => {
+        let end = self.next_boundary(self.cursor);
+        self.remove(self.cursor, end);
+      }
+
+      Edit::KillWordBackward => self.kill(self.previous_word(), self.cursor, killing),
+      Edit::KillWordForward => self.kill(self.cursor, self.next_word(), killing),
+      Edit::KillToEnd => self.kill(self.cursor, self.value.len(), killing),
+      Edit::KillLine => self.kill(0, self.value.len(), killing),
+
+      Edit::Yank => {
+        if let Some(text) = self.kill_ring.last().cloned() {
+          self.yank(text, self.kill_ring.len() - 1);
+        }
+      }
+
+      // Replaces the text that was just yanked with the previous entry of the
+      // kill ring. This does nothing if the last edit was not a yank.
+      Edit::YankPop => {
+        if let Some((start, end, index)) = yanked {
+          let index = match index {
+            0 => self.kill_ring.len() - 1,
+            index => index - 1,
+          };
+
+          self.remove(start, end);
+          self.yank(self.kill_ring[index].clone(), index);
+        }
+      }
+    }
+  }
+
+  // Returns the visible part of the value for a field of `width` columns, and
+  // the column of the cursor within it. Each grapheme is displayed through
+  // `display`, which receives its index, so secrets can be masked. The view is
+  // scrolled horizontally to keep the cursor visible.
+  pub fn view<F>(&mut self, width: u16, display: F) -> (String, u16)
+  where
+    F: Fn(usize, &str) -> String,
+  {
+    let width = width as usize;
+
+    let cells: Vec<(String, usize)> = self
+      .value
+      .graphemes(true)
+      .enumerate()
+      .map(|(index, grapheme)| {
+        let cell = display(index, grapheme);
+        let cell_width = cell.width();
+
+        (cell, cell_width)
+      })
+      .collect();
+
+    let cursor = self.value[..self.cursor].graphemes(true).count();
+    let columns = |range: &[(String, usize)]| range.iter().map(|(_, width)| width).sum::<usize>();
+    // The cursor takes one column when it is at the end of the value.
+    let cursor_width = cells.get(cursor).map(|(_, width)| *width).unwrap_or(1).max(1);
+
+    self.scroll = self.scroll.min(cursor);
+
+    while self.scroll < cursor && columns(&cells[self.scroll..cursor]) + cursor_width > width {
+      self.scroll += 1;
+    }
+
+    // Scroll back when text was removed, so the field does not show empty space
+    // while part of the value is hidden, leaving a column for the cursor at the
+    // end of the value.
+    while self.scroll > 0 && columns(&cells[self.scroll - 1..]) < width {
+      self.scroll -= 1;
+    }
+
+    let mut visible = String::new();
+    let mut used = 0;
+
+    for (cell, cell_width) in &cells[self.scroll..] {
+      if used + cell_width > width {
+        break;
+      }
+
+      visible.push_str(cell);
+      used += cell_width;
+    }
+
+    (visible, columns(&cells[self.scroll..cursor]).min(width) as u16)
+  }
+
+  // Removes everything from memory, including the kill ring.
+  pub fn zeroize(&mut self) {
+    self.value.zeroize();
+    self.kill_ring.zeroize();
+    self.cursor = 0;
+    self.killing = false;
+    self.yanked = None;
+    self.scroll = 0;
+  }
+
+  fn remove(&mut self, start: usize, end: usize) {
+    self.value.replace_range(start..end, "");
+    self.cursor = start;
+  }
+
+  // Removes a range of text into the kill ring. Consecutive kills are merged
+  // into a single entry, in the order the text appeared.
+  fn kill(&mut self, start: usize, end: usize, append: bool) {
+    if start == end {
+      self.killing = append;
+      return;
+    }
+
+    let backward = end == self.cursor;
+    let text = self.value[start..end].to_string();
+
+    self.remove(start, end);
+
+    match self.kill_ring.last_mut() {
+      Some(last) if append && backward => last.insert_str(0, &text),
+      Some(last) if append => last.push_str(&text),
+
+      _ => {
+        if self.kill_ring.len() == KILL_RING_SIZE {
+          self.kill_ring.remove(0).zeroize();
+        }
+
+        self.kill_ring.push(text);
+      }
+    }
+
+    self.killing = true;
+  }
+
+  fn yank(&mut self, text: String, index: usize) {
+    let start = self.cursor;
+
+    self.value.insert_str(start, &text);
+    self.cursor = start + text.len();
+    self.yanked = Some((start, self.cursor, index));
+  }
+
+  // Moves the cursor forward to the closest grapheme boundary, in case the last
+  // inserted character merged with the following grapheme.
+  fn snap(&mut self) {
+    if !self.value.grapheme_indices(true).any(|(index, _)| index == self.cursor) {
+      self.cursor = self.next_boundary(self.cursor);
+    }
+  }
+
+  fn previous_boundary(&self, from: usize) -> usize {
+    self.value[..from].grapheme_indices(true).next_back().map(|(index, _)| index).unwrap_or(0)
+  }
+
+  fn next_boundary(&self, from: usize) -> usize {
+    self
+      .value
+      .grapheme_indices(true)
+      .map(|(index, _)| index)
+      .find(|index| *index > from)
+      .unwrap_or(self.value.len())
+  }
+
+  // Start of the word before the cursor, skipping any separators first.
+  fn previous_word(&self) -> usize {
+    let mut seen_word = false;
+
+    for (index, grapheme) in self.value[..self.cursor].grapheme_indices(true).rev() {
+      match (seen_word, is_word(grapheme)) {
+        (_, true) => seen_word = true,
+        (true, false) => return index + grapheme.len(),
+        (false, false) => {}
+      }
+    }
+
+    0
+  }
+
+  // End of the word after the cursor, skipping any separators first.
+  fn next_word(&self) -> usize {
+    let mut seen_word = false;
+
+    for (index, grapheme) in self.value[self.cursor..].grapheme_indices(true) {
+      match (seen_word, is_word(grapheme)) {
+        (_, true) => seen_word = true,
+        (true, false) => return self.cursor + index,
+        (false, false) => {}
+      }
+    }
+
+    self.value.len()
+  }
+}
+
+fn is_word(grapheme: &str) -> bool {
+  grapheme.chars().next().is_some_and(char::is_alphanumeric)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{Edit, LineEditor, KILL_RING_SIZE};
+
+  fn with(value: &str) -> LineEditor {
+    LineEditor::from(value.to_string())
+  }
+
+  fn apply(editor: &mut LineEditor, edits: &[Edit]) {
+    for edit in edits {
+      editor.apply(*edit);
+    }
+  }
+
+  #[test]
+  fn consecutive_kills_are_merged() {
+    let mut editor = with("foo bar baz");
+
+    apply(&mut editor, &[Edit::KillWordBackward, Edit::KillWordBackward]);
+    assert_eq!(editor.as_str(), "foo ");
+    assert_eq!(editor.kill_ring, ["bar baz"]);
+
+    // Moving the cursor ends the sequence of kills.
+    apply(&mut editor, &[Edit::Left, Edit::KillWordBackward]);
+    assert_eq!(editor.as_str(), " ");
+    assert_eq!(editor.kill_ring, ["bar baz", "foo"]);
+
+    let mut editor = with("one two three");
+
+    apply(&mut editor, &[Edit::Start, Edit::KillWordForward, Edit::KillWordForward]);
+    assert_eq!(editor.as_str(), " three");
+    assert_eq!(editor.kill_ring, ["one two"]);
+  }
+
+  #[test]
+  fn kill_ring_is_capped() {
+    let mut editor = LineEditor::default();
+
+    for index in 0..KILL_RING_SIZE + 4 {
+      editor.set(format!("w{index}"));
+      editor.apply(Edit::KillLine);
+    }
+
+    assert_eq!(editor.kill_ring.len(), KILL_RING_SIZE);
+    assert_eq!(editor.kill_ring.first().map(String::as_str), Some("w4"));
+    assert_eq!(editor.kill_ring.last().map(String::as_str), Some("w19"));
+  }
+
+  #[test]
+  fn yank_and_yank_pop() {
+    let mut editor = LineEditor::default();
+
+    for text in ["a", "b", "c"] {
+      editor.set(text.to_string());
+      editor.apply(Edit::KillLine);
+    }
+
+    editor.apply(Edit::Yank);
+    assert_eq!(editor.as_str(), "c");
+
+    editor.apply(Edit::YankPop);
+    assert_eq!(editor.as_str(), "b");
+
+    editor.apply(Edit::YankPop);
+    assert_eq!(editor.as_str(), "a");
+
+    // The kill ring wraps around.
+    editor.apply(Edit::YankPop);
+    assert_eq!(editor.as_str(), "c");
+    assert_eq!(editor.cursor, 1);
+  }
+
+  #[test]
+  fn yank_pop_needs_a_yank() {
+    let mut editor = LineEditor::default();
+
+    editor.set("killed".to_string());
+    editor.apply(Edit::KillLine);
+
+    editor.apply(Edit::YankPop);
+    assert_eq!(editor.as_str(), "");
+
+    apply(&mut editor, &[Edit::Yank, Edit::Insert('!'), Edit::YankPop]);
+    assert_eq!(editor.as_str(), "killed!");
+  }
+
+  #[test]
+  fn words_stop_at_punctuation() {
+    let mut editor = with("foo-bar.baz qux");
+    editor.apply(Edit::Start);
+
+    let mut stops = Vec::new();
+
+    for _ in 0..4 {
+      editor.apply(Edit::WordRight);
+      stops.push(editor.cursor);
+    }
+
+    assert_eq!(stops, [3, 7, 11, 15]);
+
+    stops.clear();
+
+    for _ in 0..4 {
+      editor.apply(Edit::WordLeft);
+      stops.push(editor.cursor);
+    }
+
+    assert_eq!(stops, [12, 8, 4, 0]);
+
+    let mut editor = with("/usr/local/bin/");
+    editor.apply(Edit::KillWordBackward);
+    assert_eq!(editor.as_str(), "/usr/local/");
+  }
+
+  #[test]
+  fn moves_over_whole_graphemes() {
+    // An accent combined with its letter, a flag made of two code points, and
+    // a wide character.
+    let mut editor = with("e\u{301}🇫🇷漢");
+    assert_eq!(editor.len(), 3);
+
+    let mut stops = Vec::new();
+
+    for _ in 0..4 {
+      editor.apply(Edit::Left);
+      stops.push(editor.cursor);
+    }
+
+    assert_eq!(stops, [11, 3, 0, 0]);
+
+    apply(&mut editor, &[Edit::Right, Edit::DeleteForward]);
+    assert_eq!(editor.as_str(), "e\u{301}漢");
+
+    apply(&mut editor, &[Edit::End, Edit::DeleteBackward]);
+    assert_eq!(editor.as_str(), "e\u{301}");
+
+    // A combining character typed after a letter joins its grapheme.
+    let mut editor = with("e");
+    editor.apply(Edit::Insert('\u{301}'));
+    assert_eq!((editor.len(), editor.cursor), (1, 3));
+  }
+
+  #[test]
+  fn view_scrolls_to_the_cursor() {
+    let identity = |_: usize, grapheme: &str| grapheme.to_string();
+
+    let mut editor = with("漢字かな");
+    assert_eq!(editor.view(5, identity), ("かな".to_string(), 4));
+
+    editor.apply(Edit::Start);
+    assert_eq!(editor.view(5, identity), ("漢字".to_string(), 0));
+
+    // Secrets are masked cell by cell.
+    assert_eq!(editor.view(10, |_, _| "*".to_string()), ("****".to_string(), 0));
+
+    let mut editor = with("abcdefgh");
+    assert_eq!(editor.view(4, identity), ("fgh".to_string(), 3));
+
+    // Removing text scrolls back, so the field is filled again.
+    apply(&mut editor, &[Edit::DeleteBackward, Edit::DeleteBackward, Edit::DeleteBackward]);
+    assert_eq!(editor.view(4, identity), ("cde".to_string(), 3));
+
+    assert_eq!(editor.view(0, identity), (String::new(), 0));
+  }
+}
@@ -0,0 +1,197 @@
+use unicode_width::UnicodeWidthStr;
+
+use crate::ui::util::display_width;
+
+// Large fonts used to draw the clock, without depending on external tools.
+//
+// Each glyph is a list of rows of the same width. Fonts defined with a `pixel`
+// string are bitmaps, where every `#` is replaced with that string, and every
+// space with blanks of the same width.
+pub struct Font {
+  pub name: &'static str,
+  height: usize,
+  pixel: Option<&'static str>,
+  glyphs: &'static [(char, &'static [&'static str])],
+}
+
+pub const FONTS: &[Font] = &[BLOCK, LINES, SEGMENT];
+
+const BLOCK: Font = Font {
+  name: "block",
+  height: 5,
+  pixel: Some("██"),
+  glyphs: &[
+    ('0', &["###", "# #", "# #", "# #", "###"]),
+    ('1', &["  #", "  #", "  #", "  #", "  #"]),
+    ('2', &["###", "  #", "###", "#  ", "###"]),
+    ('3', &["###", "  #", "###", "  #", "###"]),
+    ('4', &["# #", "# #", "###", "  #", "  #"]),
+    ('5', &["###", "#  ", "###", "  #", "###"]),
+    ('6', &["###", "#  ", "###", "# #", "###"]),
+    ('7', &["###", "  #", "  #", "  #", "  #"]),
+    ('8', &["###", "# #", "###", "# #", "###"]),
+    ('9', &["###", "# #", "###", "  #", "###"]),
+    (':', &[" ", "#", " ", "#", " "]),
+    (' ', &[" ", " ", " ", " ", " "]),
+  ],
+};
+
+const LINES: Font = Font {
+  name: "lines",
+  height: 3,
+  pixel: None,
+  glyphs: &[
+    ('0', &["┌─┐", "│ │", "└─┘"]),
+    ('1', &["  ╷", "  │", "  ╵"]),
+    ('2', &["╶─┐", "┌─┘", "└─╴"]),
+    ('3', &["╶─┐", " ─┤", "╶─┘"]),
+    ('4', &["╷ ╷", "└─┤", "  ╵"]),
+    ('5', &["┌─╴", "└─┐", "╶─┘"]),
+    ('6', &["┌─╴", "├─┐", "└─┘"]),
+    ('7', &["╶─┐", "  │", "  ╵"]),
+    ('8', &["┌─┐", "├─┤", "└─┘"]),
+    ('9', &["┌─┐", "└─┤", "╶─┘"]),
+    (':', &[" ", "·", "·"]),
+    (' ', &[" ", " ", " "]),
+  ],
+};
+
+const SEGMENT: Font = Font {
+  name: "segment",
+  height: 3,
+  pixel: None,
+  glyphs: &[
+    ('0', &[" _ ", "| |", "|_|"]),
+    ('1', &["   ", "  |", "  |"]),
+    ('2', &[" _ ", " _|", "|_ "]),
+    ('3', &[" _ ", " _|", " _|"]),
+    ('4', &["   ", "|_|", "  |"]),
+    ('5', &[" _ ", "|_ ", " _|"]),
+    ('6', &[" _ ", "|_ ", "|_|"]),
+    ('7', &[" _ ", "  |", "  |"]),
+    ('8', &[" _ ", "|_|", "|_|"]),
+    ('9', &[" _ ", "|_|", " _|"]),
+    (':', &[" ", ".", "."]),
+    (' ', &[" ", " ", " "]),
+  ],
+};
+
+impl Font {
+  pub fn find(name: &str) -> Option<&'static Font> {
+    FONTS.iter().find(|font| font.name == name)
+  }
+
+  // Renders a string into rows of large characters.
+  //
+  // Characters that do not have a glyph in the font are drawn in their regular
+  // size on the bottom row, so suffixes like AM/PM can still be displayed.
+  pub fn render(&self, text: &str) -> Vec<String> {
+    let mut rows = vec![String::new(); self.height];
+    let mut previous_glyph = None;
+
+    for c in text.chars() {
+      let glyph = self.glyphs.iter().find(|(glyph, _)| *glyph == c);
+
+      // Regular-sized characters following each other are not spaced out.
+      if matches!((previous_glyph, glyph), (Some(Some(_)), _) | (Some(None), Some(_))) {
+        for row in rows.iter_mut() {
+          row.push_str(self.spacing());
+        }
+      }
+
+      previous_glyph = Some(glyph);
+
+      match glyph {
+        Some((_, glyph)) => {
+          for (row, line) in rows.iter_mut().zip(glyph.iter()) {
+            match self.pixel {
+              Some(pixel) => {
+                for dot in line.chars() {
+                  match dot {
+                    '#' => row.push_str(pixel),
+                    _ => row.push_str(&" ".repeat(pixel.width())),
+                  }
+                }
+              }
+
+              None => row.push_str(line),
+            }
+          }
+        }
+
+        // The other rows are filled with as many blanks as the character takes
+        // columns, so wide characters do not shift the following glyphs.
+        None => {
+          let blank = " ".repeat(display_width(c.encode_utf8(&mut [0; 4])) as usize);
+
+          for (index, row) in rows.iter_mut().enumerate() {
+            match index == self.height - 1 {
+              true => row.push(c),
+              false => row.push_str(&blank),
+            }
+          }
+        }
+      }
+    }
+
+    rows
+  }
+
+  fn spacing(&self) -> &'static str {
+    match self.pixel {
+      Some(_) => "  ",
+      None => " ",
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{Font, FONTS};
+  use crate::ui::util::display_width;
+
+  fn widths(rows: &[String]) -> Vec<u16> {
+    rows.iter().map(|row| display_width(row)).collect()
+  }
+
+  #[test]
+  fn renders_times() {
+    for (name, width) in [("block", 34), ("lines", 17), ("segment", 17)] {
+      let font = Font::find(name).unwrap();
+      let rows = font.render("12:34");
+
+      assert_eq!(rows.len(), font.height, "{}", name);
+      assert_eq!(widths(&rows), vec![width; font.height], "{}", name);
+    }
+
+    assert_eq!(Font::find("segment").unwrap().render("12:34"), [
+      "     _     _     ",
+      "  |  _| .  _| |_|",
+      "  | |_  .  _|   |",
+    ]);
+  }
+
+  #[test]
+  fn renders_regular_characters() {
+    for font in FONTS {
+      let plain = display_width(&font.render("12:34")[0]);
+      let spacing = display_width(font.spacing());
+      let space = display_width(&font.render(" ")[0]);
+      let colon = display_width(&font.render(":")[0]);
+
+      // Characters without a glyph follow the spacing of the font, and are
+      // written on the last row.
+      let rows = font.render("12:34 PM");
+      assert_eq!(widths(&rows), vec![plain + spacing + space + spacing + 2; font.height], "{}", font.name);
+      assert!(rows[font.height - 1].ends_with(" PM"), "{}", font.name);
+
+      // Wide characters take two columns on every row.
+      let rows = font.render("12時34分");
+      assert_eq!(widths(&rows), vec![plain - colon + 2 + spacing + 2; font.height], "{}", font.name);
+
+      // Combining characters take none.
+      let rows = font.render("12:34 e\u{301}");
+      assert_eq!(widths(&rows), vec![plain + spacing + space + spacing + 1; font.height], "{}", font.name);
+    }
+  }
+}
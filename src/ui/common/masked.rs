@@ -1,20 +1,25 @@
 use zeroize::Zeroize;
 
+use super::editor::LineEditor;
+
 #[derive(Default)]
 pub struct MaskedString {
-  pub value: String,
+  pub value: LineEditor,
   pub mask: Option<String>,
 }
 
 impl MaskedString {
   pub fn from(value: String, mask: Option<String>) -> MaskedString {
-    MaskedString { value, mask }
+    MaskedString {
+      value: LineEditor::from(value),
+      mask,
+    }
   }
 
   pub fn get(&self) -> &str {
     match self.mask {
       Some(ref mask) => mask,
-      None => &self.value,
+      None => self.value.as_str(),
     }
   }
 
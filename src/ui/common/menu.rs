@@ -1,26 +1,68 @@
-use std::{borrow::Cow, error::Error};
+use std::{
+  borrow::Cow,
+  error::Error,
+  sync::{
+    atomic::{AtomicUsize, Ordering},
+    Mutex,
+  },
+};
 
 use tui::{
-  prelude::Rect,
+  prelude::{Position, Rect},
   style::{Modifier, Style},
-  text::Span,
+  text::{Line, Span},
   widgets::{Block, BorderType, Borders, Paragraph},
 };
 
 use crate::{
   ui::{
-    util::{get_rect_bounds, titleize},
+    util::{center_rect, display_width, pad_to_width, titleize},
     Frame,
   },
   Greeter,
 };
 
-use super::style::Themed;
+use super::style::{Theme, Themed};
 
 pub trait MenuItem {
   fn format(&self) -> Cow<'_, str>;
+
+  // Secondary text displayed, dimmed, after the entry.
+  fn subtitle(&self) -> Option<Cow<'_, str>> {
+    None
+  }
+}
+
+// Space between an entry and its subtitle.
+const SUBTITLE_GAP: &str = "  ";
+
+// Navigation shared by every menu, regardless of the type of its options.
+pub trait MenuControl {
+  fn up(&mut self);
+  fn down(&mut self);
+  fn page_up(&mut self);
+  fn page_down(&mut self);
+  fn first(&mut self);
+  fn last(&mut self);
+  fn scroll_up(&mut self);
+  fn scroll_down(&mut self);
+
+  fn option_at(&self, position: Position) -> Option<usize>;
+  fn is_selected(&self, index: usize) -> bool;
+  fn select(&mut self, index: usize);
+
+  fn filter(&self) -> &str;
+  fn push_filter(&mut self, c: char);
+  fn pop_filter(&mut self);
+  fn clear_filter(&mut self);
 }
 
+// Width and height taken by the borders and the padding around the entries.
+const MENU_PADDING: u16 = 4;
+// Number of entries skipped by moving a page up or down, until the menu is
+// drawn and the number of visible entries is known.
+const PAGE_SIZE: usize = 10;
+
 #[derive(Default)]
 pub struct Menu<T>
 where
@@ -28,36 +70,139 @@ where
 {
   pub title: String,
   pub options: Vec<T>,
+  // Index of the selected option in `options`.
   pub selected: usize,
+  // Text typed to narrow down the displayed options.
+  pub filter: String,
+  // Index, among the visible options, of the first one displayed. This is
+  // updated while drawing, which only borrows the menu immutably.
+  pub offset: AtomicUsize,
+  // Number of options that fit in the menu when it was last drawn.
+  pub rows: AtomicUsize,
+  // Area the options were drawn in, to find the option under the mouse.
+  pub area: Mutex<Rect>,
 }
 
 impl<T> Menu<T>
 where
   T: MenuItem,
 {
-  pub fn draw(&self, greeter: &Greeter, f: &mut Frame) -> Result<(u16, u16), Box<dyn Error>> {
-    let theme = &greeter.theme;
+  pub fn new(title: String, options: Vec<T>) -> Menu<T> {
+    Menu {
+      title,
+      options,
+      selected: 0,
+      filter: String::new(),
+      offset: AtomicUsize::new(0),
+      rows: AtomicUsize::new(0),
+      area: Mutex::new(Rect::default()),
+    }
+  }
+
+  // Returns the selected option, unless it is hidden by the filter.
+  pub fn selected(&self) -> Option<&T> {
+    match self.visible().contains(&self.selected) {
+      true => self.options.get(self.selected),
+      false => None,
+    }
+  }
 
-    let size = f.size();
-    let (x, y, width, height) = get_rect_bounds(greeter, size, self.options.len());
+  // Returns the indices of the options matching the filter.
+  fn visible(&self) -> Vec<usize> {
+    let filter = self.filter.to_lowercase();
+
+    self
+      .options
+      .iter()
+      .enumerate()
+      .filter(|(_, option)| filter.is_empty() || option.format().to_lowercase().contains(&filter))
+      .map(|(index, _)| index)
+      .collect()
+  }
+
+  // Moves the selection by a number of visible entries, either wrapping around
+  // or stopping at the ends of the list.
+  fn step(&mut self, delta: isize, wrap: bool) {
+    let visible = self.visible();
+
+    if visible.is_empty() {
+      return;
+    }
+
+    let len = visible.len() as isize;
+    let position = visible.iter().position(|index| *index == self.selected).map(|position| position as isize);
+
+    let position = match position {
+      None if delta < 0 => len - 1,
+      None => 0,
+      Some(position) if wrap => (position + delta).rem_euclid(len),
+      Some(position) => (position + delta).clamp(0, len - 1),
+    };
+
+    self.selected = visible[position as usize];
+  }
+
+  fn page_size(&self) -> usize {
+    match self.rows.load(Ordering::Relaxed) {
+      0 => PAGE_SIZE,
+      rows => rows,
+    }
+  }
+
+  pub fn draw(&self, greeter: &Greeter, f: &mut Frame, area: Rect) -> Result<(u16, u16), Box<dyn Error>> {
+    let theme = &greeter.settings.theme;
+    let visible = self.visible();
+
+    let title = match self.filter.is_empty() {
+      true => titleize(&self.title),
+      false => titleize(&format!("{} [{}]", self.title, self.filter)),
+    };
+
+    // The menu is as wide as its longest entry, or its title, plus the borders
+    // and padding on each side.
+    let content_width = visible
+      .iter()
+      .map(|index| {
+        let option = &self.options[*index];
+
+        let subtitle = option.subtitle().map(|subtitle| display_width(SUBTITLE_GAP) + display_width(&subtitle));
+
+        display_width(&option.format()) + subtitle.unwrap_or_default()
+      })
+      .chain([display_width(&title)])
+      .max()
+      .unwrap_or_default();
+
+    // The menu is as high as its entries, plus the borders and padding, and is
+    // clamped to the terminal, in which case it scrolls.
+    let (x, y, width, height) = center_rect(
+      area,
+      content_width.saturating_add(MENU_PADDING),
+      (visible.len().max(1) as u16).saturating_add(MENU_PADDING),
+    );
 
     let container = Rect::new(x, y, width, height);
+    let rows = height.saturating_sub(MENU_PADDING) as usize;
+
+    self.scroll(&visible, rows);
+
+    if let Ok(mut area) = self.area.lock() {
+      *area = Rect::new(x + 2, y + 2, width.saturating_sub(MENU_PADDING), rows as u16);
+    }
+
+    let offset = self.offset.load(Ordering::Relaxed);
 
-    let title = Span::from(titleize(&self.title));
     let block = Block::default()
-      .title(title)
+      .title(Span::from(title))
       .title_style(theme.of(&[Themed::Title]))
-      .style(theme.of(&[Themed::Container]))
+      .style(theme.of(&[Themed::Menu]))
       .borders(Borders::ALL)
-      .border_type(BorderType::Plain)
+      .border_type(theme.border_type(Themed::Menu, BorderType::Plain))
       .border_style(theme.of(&[Themed::Border]));
 
-    for (index, option) in self.options.iter().enumerate() {
-      let name = option.format();
-      let name = format!("{:1$}", name, greeter.width() as usize - 4);
-
-      let frame = Rect::new(x + 2, y + 2 + index as u16, width - 4, 1);
-      let option_text = self.get_option(name, index);
+    for (row, index) in visible.iter().skip(offset).take(rows).enumerate() {
+      let frame = Rect::new(x + 2, y + 2 + row as u16, width.saturating_sub(MENU_PADDING), 1);
+      let option_text = self.get_option(theme, &self.options[*index], *index, frame.width);
       let option = Paragraph::new(option_text);
 
       f.render_widget(option, frame);
@@ -65,17 +210,332 @@ where
 
     f.render_widget(block, container);
 
+    // Show that entries are hidden above or below the viewport.
+    if width > MENU_PADDING {
+      let indicator_x = x + width - 3;
+
+      if offset > 0 {
+        f.render_widget(Paragraph::new("▲").style(theme.of(&[Themed::Border])), Rect::new(indicator_x, y, 1, 1));
+      }
+
+      if offset + rows < visible.len() {
+        f.render_widget(Paragraph::new("▼").style(theme.of(&[Themed::Border])), Rect::new(indicator_x, y + height - 1, 1, 1));
+      }
+    }
+
     Ok((1, 1))
   }
 
-  fn get_option<'g, S>(&self, name: S, index: usize) -> Span<'g>
-  where
-    S: Into<String>,
-  {
-    if self.selected == index {
-      Span::styled(name.into(), Style::default().add_modifier(Modifier::REVERSED))
-    } else {
-      Span::from(name.into())
+  // Moves the viewport so the selected option is visible.
+  fn scroll(&self, visible: &[usize], rows: usize) {
+    self.rows.store(rows, Ordering::Relaxed);
+
+    let mut offset = self.offset.load(Ordering::Relaxed);
+
+    if let Some(position) = visible.iter().position(|index| *index == self.selected) {
+      if position < offset {
+        offset = position;
+      } else if rows > 0 && position >= offset + rows {
+        offset = position + 1 - rows;
+      }
+    }
+
+    self.offset.store(offset.min(visible.len().saturating_sub(rows)), Ordering::Relaxed);
+  }
+
+  // Returns the line of an option, padded to the width of the menu so the
+  // selection spans all of it.
+  fn get_option<'g>(&self, theme: &Theme, option: &T, index: usize, width: u16) -> Line<'g> {
+    let style = match self.selected == index {
+      true => theme.of(&[Themed::Selection]),
+      false => Style::default(),
+    };
+
+    let name = option.format();
+
+    let Some(subtitle) = option.subtitle() else {
+      return Line::from(Span::styled(pad_to_width(&name, width), style));
+    };
+
+    let subtitle = pad_to_width(&format!("{SUBTITLE_GAP}{subtitle}"), width.saturating_sub(display_width(&name)));
+
+    Line::from(vec![
+      Span::styled(name.into_owned(), style),
+      Span::styled(subtitle, style.add_modifier(Modifier::DIM)),
+    ])
+  }
+}
+
+impl<T> MenuControl for Menu<T>
+where
+  T: MenuItem,
+{
+  fn filter(&self) -> &str {
+    &self.filter
+  }
+
+  fn up(&mut self) {
+    self.step(-1, true);
+  }
+
+  fn down(&mut self) {
+    self.step(1, true);
+  }
+
+  fn page_up(&mut self) {
+    self.step(-(self.page_size() as isize), false);
+  }
+
+  fn page_down(&mut self) {
+    self.step(self.page_size() as isize, false);
+  }
+
+  fn first(&mut self) {
+    if let Some(index) = self.visible().first() {
+      self.selected = *index;
+    }
+  }
+
+  fn last(&mut self) {
+    if let Some(index) = self.visible().last() {
+      self.selected = *index;
+    }
+  }
+
+  // The mouse wheel stops at the ends of the list instead of wrapping around.
+  fn scroll_up(&mut self) {
+    self.step(-1, false);
+  }
+
+  fn scroll_down(&mut self) {
+    self.step(1, false);
+  }
+
+  // Returns the index of the option drawn at a position of the screen.
+  fn option_at(&self, position: Position) -> Option<usize> {
+    let area = *self.area.lock().ok()?;
+
+    if !area.contains(position) {
+      return None;
+    }
+
+    let row = (position.y - area.y) as usize;
+
+    self.visible().get(self.offset.load(Ordering::Relaxed) + row).copied()
+  }
+
+  fn is_selected(&self, index: usize) -> bool {
+    self.selected == index
+  }
+
+  fn select(&mut self, index: usize) {
+    self.selected = index;
+  }
+
+  // Adds a character to the filter, selecting the first match if the selected
+  // option does not match anymore.
+  fn push_filter(&mut self, c: char) {
+    self.filter.push(c);
+
+    if self.selected().is_none() {
+      self.first();
     }
   }
+
+  fn pop_filter(&mut self) {
+    self.filter.pop();
+  }
+
+  fn clear_filter(&mut self) {
+    self.filter.clear();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::borrow::Cow;
+
+  use tui::{backend::TestBackend, Terminal};
+
+  use super::{Menu, MenuControl, MenuItem};
+  use crate::Greeter;
+
+  struct Item(String);
+
+  impl MenuItem for Item {
+    fn format(&self) -> Cow<'_, str> {
+      Cow::Borrowed(&self.0)
+    }
+  }
+
+  fn menu(count: usize) -> Menu<Item> {
+    Menu::new("Menu".to_string(), (0..count).map(|index| Item(format!("Entry {index}"))).collect())
+  }
+
+  // Draws a menu and returns the text of each row of the terminal.
+  fn draw(greeter: &Greeter, menu: &Menu<Item>, width: u16, height: u16) -> Vec<String> {
+    let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+
+    terminal
+      .draw(|f| {
+        menu.draw(greeter, f, f.area()).unwrap();
+      })
+      .unwrap();
+
+    let buffer = terminal.backend().buffer();
+
+    (0..height)
+      .map(|y| (0..width).map(|x| buffer[(x, y)].symbol()).collect::<String>())
+      .collect()
+  }
+
+  fn shows(rows: &[String], entry: &str) -> bool {
+    rows.iter().any(|row| row.contains(&format!("{entry} ")))
+  }
+
+  #[test]
+  fn shows_all_entries_that_fit() {
+    for container_padding in [0, 1, 3] {
+      let mut greeter = Greeter::default();
+      greeter.settings.container_padding = container_padding;
+
+      let menu = menu(2);
+      let rows = draw(&greeter, &menu, 40, 6);
+
+      assert!(shows(&rows, "Entry 0") && shows(&rows, "Entry 1"), "{:?}", rows);
+    }
+  }
+
+  #[test]
+  fn keeps_the_selection_visible_when_clamped() {
+    let greeter = Greeter::default();
+
+    let mut menu = menu(30);
+    menu.selected = 25;
+
+    let rows = draw(&greeter, &menu, 40, 10);
+
+    assert!(shows(&rows, "Entry 25"), "{:?}", rows);
+    assert!(!shows(&rows, "Entry 0"), "{:?}", rows);
+    assert_eq!(rows.iter().filter(|row| row.contains("Entry")).count(), 6);
+  }
+
+  #[test]
+  fn empty_menus() {
+    let mut menu = menu(0);
+
+    menu.up();
+    menu.down();
+    menu.page_up();
+    menu.page_down();
+    menu.first();
+    menu.last();
+    menu.scroll_up();
+    menu.scroll_down();
+    menu.push_filter('x');
+
+    assert_eq!(menu.selected, 0);
+    assert!(menu.selected().is_none());
+
+    let rows = draw(&Greeter::default(), &menu, 40, 10);
+
+    assert!(rows.iter().any(|row| row.contains("Menu")), "{:?}", rows);
+  }
+
+  #[test]
+  fn wraps_around() {
+    let mut menu = menu(3);
+
+    menu.up();
+    assert_eq!(menu.selected, 2);
+
+    menu.down();
+    assert_eq!(menu.selected, 0);
+
+    menu.down();
+    menu.down();
+    menu.down();
+    assert_eq!(menu.selected, 0);
+
+    // The mouse wheel does not wrap around.
+    menu.scroll_up();
+    assert_eq!(menu.selected, 0);
+
+    menu.last();
+    menu.scroll_down();
+    assert_eq!(menu.selected, 2);
+  }
+
+  #[test]
+  fn pages_stop_at_the_ends() {
+    let mut short = menu(3);
+
+    short.page_down();
+    assert_eq!(short.selected, 2);
+
+    short.page_up();
+    assert_eq!(short.selected, 0);
+
+    let mut menu = menu(25);
+
+    menu.page_down();
+    assert_eq!(menu.selected, 10);
+
+    menu.page_down();
+    menu.page_down();
+    assert_eq!(menu.selected, 24);
+
+    menu.page_up();
+    assert_eq!(menu.selected, 14);
+
+    menu.page_up();
+    menu.page_up();
+    assert_eq!(menu.selected, 0);
+
+    // Once drawn, pages are as long as the visible entries.
+    draw(&Greeter::default(), &menu, 40, 10);
+    menu.page_down();
+    assert_eq!(menu.selected, 6);
+  }
+
+  #[test]
+  fn filters() {
+    let mut menu = menu(12);
+    menu.selected = 5;
+
+    // The selection is kept as long as it matches.
+    menu.push_filter('5');
+    assert_eq!(menu.selected().map(|item| item.0.as_str()), Some("Entry 5"));
+
+    menu.pop_filter();
+    menu.push_filter('1');
+    assert_eq!(menu.selected, 1);
+
+    menu.down();
+    assert_eq!(menu.selected, 10);
+
+    menu.down();
+    menu.down();
+    assert_eq!(menu.selected, 1);
+
+    menu.last();
+    assert_eq!(menu.selected, 11);
+
+    // Nothing matches, so nothing is selected, and moving around does nothing.
+    menu.push_filter('x');
+    assert!(menu.selected().is_none());
+
+    menu.up();
+    menu.page_down();
+    menu.first();
+    menu.last();
+    assert_eq!(menu.selected, 11);
+    assert!(menu.selected().is_none());
+
+    let rows = draw(&Greeter::default(), &menu, 40, 10);
+    assert!(!rows.iter().any(|row| row.contains("Entry")), "{:?}", rows);
+
+    menu.clear_filter();
+    assert_eq!(menu.selected().map(|item| item.0.as_str()), Some("Entry 11"));
+  }
 }
@@ -1,3 +1,6 @@
+pub mod color;
+pub mod editor;
+pub mod font;
 pub mod masked;
 pub mod menu;
 pub mod style;
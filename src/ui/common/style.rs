@@ -1,8 +1,21 @@
-use std::str::FromStr;
+use std::{error::Error, fs, path::Path, str::FromStr};
 
-use tui::style::{Color, Style};
+use rust_embed::RustEmbed;
+use tui::{
+  style::{Color, Modifier, Style},
+  widgets::BorderType,
+};
 
-#[derive(Clone)]
+use super::color::ColorDepth;
+
+// Directories theme files are looked up in, by order of precedence.
+const THEME_DIRS: &[&str] = &["/etc/tuigreet/themes", "/usr/share/tuigreet/themes"];
+
+#[derive(RustEmbed)]
+#[folder = "contrib/themes"]
+struct BuiltinThemes;
+
+#[derive(Clone, Copy)]
 enum Component {
   Bg,
   Fg,
@@ -10,7 +23,10 @@ enum Component {
 
 pub enum Themed {
   Container,
+  Menu,
   Time,
+  Clock,
+  Date,
   Text,
   Border,
   Title,
@@ -19,73 +35,255 @@ pub enum Themed {
   Input,
   Action,
   ActionButton,
+  Selection,
+  Error,
+  Caps,
+}
+
+// Style of a themed element, and the type of its borders for the elements
+// drawn as a block.
+#[derive(Clone, Copy, Default)]
+struct Spec {
+  style: Style,
+  border: Option<BorderType>,
 }
 
 #[derive(Default)]
 pub struct Theme {
-  container: Option<(Component, Color)>,
-  time: Option<(Component, Color)>,
-  text: Option<(Component, Color)>,
-  border: Option<(Component, Color)>,
-  title: Option<(Component, Color)>,
-  greet: Option<(Component, Color)>,
-  prompt: Option<(Component, Color)>,
-  input: Option<(Component, Color)>,
-  action: Option<(Component, Color)>,
-  button: Option<(Component, Color)>,
+  container: Option<Spec>,
+  menu: Option<Spec>,
+  time: Option<Spec>,
+  clock: Option<Spec>,
+  date: Option<Spec>,
+  text: Option<Spec>,
+  border: Option<Spec>,
+  title: Option<Spec>,
+  greet: Option<Spec>,
+  prompt: Option<Spec>,
+  input: Option<Spec>,
+  action: Option<Spec>,
+  button: Option<Spec>,
+  selection: Option<Spec>,
+  error: Option<Spec>,
+  caps: Option<Spec>,
+
+  // Directives that could not be understood while parsing the theme.
+  problems: Vec<String>,
 }
 
 impl Theme {
-  pub fn parse(spec: &str) -> Theme {
-    use Component::*;
+  // Loads a theme, either from an inline specification, made of `key=style`
+  // directives separated by semicolons, or by name. A style is a
+  // comma-separated list of colors (applied to the foreground, or to the
+  // background for containers), `fg:COLOR`, `bg:COLOR`, modifiers and, for the
+  // elements drawn with borders, a border type.
+  //
+  // Named themes are TOML files mapping the same keys to styles, looked up in
+  // the theme directories, then among the built-in themes. Both may inherit
+  // from another named theme through the `inherits` key.
+  //
+  // Themes that cannot be found or read are errors, while invalid directives
+  // are collected as problems, so they can all be reported at once.
+  pub fn load(spec: &str) -> Result<Theme, Box<dyn Error>> {
+    let mut theme = Theme::default();
+
+    theme.include(spec, &mut Vec::new())?;
+    theme.resolve();
+
+    Ok(theme)
+  }
+
+  // Applies the directives of a theme specification, after the ones of the
+  // theme it inherits from. `chain` holds the names of the themes being
+  // loaded, to detect cycles.
+  fn include(&mut self, spec: &str, chain: &mut Vec<String>) -> Result<(), Box<dyn Error>> {
+    if !spec.contains('=') {
+      return self.include_named(spec, chain);
+    }
+
+    let directives: Vec<&str> = spec.split(';').filter(|directive| !directive.is_empty()).collect();
+
+    // The inherited theme is applied first, wherever it is named.
+    for directive in &directives {
+      if let Some(("inherits", name)) = directive.split_once('=') {
+        self.include_named(name, chain)?;
+      }
+    }
 
-    let directives = spec.split(';').filter_map(|directive| directive.split_once('='));
-    let mut style = Theme::default();
-
-    for (key, value) in directives {
-      if let Ok(color) = Color::from_str(value) {
-        match key {
-          "container" => style.container = Some((Bg, color)),
-          "time" => style.time = Some((Fg, color)),
-          "text" => style.text = Some((Fg, color)),
-          "border" => style.border = Some((Fg, color)),
-          "title" => style.title = Some((Fg, color)),
-          "greet" => style.greet = Some((Fg, color)),
-          "prompt" => style.prompt = Some((Fg, color)),
-          "input" => style.input = Some((Fg, color)),
-          "action" => style.action = Some((Fg, color)),
-          "button" => style.button = Some((Fg, color)),
-          _ => {}
+    for directive in directives {
+      match directive.split_once('=') {
+        Some(("inherits", _)) => {}
+        Some((key, value)) => self.set("", key, value),
+        None => self.problems.push(format!("malformed directive '{directive}'")),
+      }
+    }
+
+    Ok(())
+  }
+
+  fn include_named(&mut self, name: &str, chain: &mut Vec<String>) -> Result<(), Box<dyn Error>> {
+    if chain.iter().any(|included| included == name) {
+      return Err(format!("theme '{name}' inherits from itself").into());
+    }
+
+    let table = read_theme(name)?;
+    let origin = format!("theme '{name}': ");
+
+    chain.push(name.to_string());
+
+    if let Some(parent) = table.get("inherits") {
+      match parent.as_str() {
+        Some(parent) => self.include_named(parent, chain)?,
+        None => {
+          let problem = format!("{origin}invalid value '{parent}' for key 'inherits': expected a theme name");
+
+          self.problems.push(problem);
         }
       }
     }
 
-    if style.time.is_none() {
-      style.time.clone_from(&style.text);
+    for (key, value) in table.iter().filter(|(key, _)| *key != "inherits") {
+      match value.as_str() {
+        Some(value) => self.set(&origin, key, value),
+        None => self.problems.push(format!("{origin}invalid value '{value}' for key '{key}': expected a string")),
+      }
     }
-    if style.greet.is_none() {
-      style.greet.clone_from(&style.text);
+
+    chain.pop();
+
+    Ok(())
+  }
+
+  // Sets the style of an element from a directive, or records why it could not
+  // be understood.
+  fn set(&mut self, origin: &str, key: &str, value: &str) {
+    use Component::*;
+
+    let (target, component, bordered) = match key {
+      "container" => (&mut self.container, Bg, true),
+      "menu" => (&mut self.menu, Bg, true),
+      "time" => (&mut self.time, Fg, false),
+      "clock" => (&mut self.clock, Fg, false),
+      "date" => (&mut self.date, Fg, false),
+      "text" => (&mut self.text, Fg, false),
+      "border" => (&mut self.border, Fg, false),
+      "title" => (&mut self.title, Fg, false),
+      "greet" => (&mut self.greet, Fg, false),
+      "prompt" => (&mut self.prompt, Fg, false),
+      "input" => (&mut self.input, Fg, false),
+      "action" => (&mut self.action, Fg, false),
+      "button" => (&mut self.button, Fg, false),
+      "selection" => (&mut self.selection, Fg, false),
+      "error" => (&mut self.error, Fg, false),
+      "caps" => (&mut self.caps, Fg, false),
+
+      _ => {
+        self.problems.push(format!("{origin}unknown key '{key}'"));
+        return;
+      }
+    };
+
+    match parse_spec(value, component, bordered) {
+      Ok(spec) => *target = Some(spec),
+      Err(problem) => self.problems.push(format!("{origin}invalid value '{value}' for key '{key}': {problem}")),
+    }
+  }
+
+  // Makes the elements that were not themed fall back to related ones.
+  fn resolve(&mut self) {
+    if self.time.is_none() {
+      self.time.clone_from(&self.text);
     }
-    if style.title.is_none() {
-      style.title.clone_from(&style.border);
+    if self.clock.is_none() {
+      self.clock.clone_from(&self.time);
     }
-    if style.button.is_none() {
-      style.button.clone_from(&style.action);
+    if self.date.is_none() {
+      self.date.clone_from(&self.time);
     }
+    if self.greet.is_none() {
+      self.greet.clone_from(&self.text);
+    }
+    if self.error.is_none() {
+      self.error.clone_from(&self.text);
+    }
+    if self.title.is_none() {
+      self.title.clone_from(&self.border);
+    }
+    if self.button.is_none() {
+      self.button.clone_from(&self.action);
+    }
+    if self.menu.is_none() {
+      self.menu.clone_from(&self.container);
+    }
+  }
+
+  // Maps the colors of the theme to the closest ones the terminal can display.
+  // Without colors, only bold and reverse video are kept, and the elements that
+  // need to stand out are given one of them.
+  pub fn downgrade(&mut self, depth: ColorDepth) {
+    let mut specs = [
+      &mut self.container,
+      &mut self.menu,
+      &mut self.time,
+      &mut self.clock,
+      &mut self.date,
+      &mut self.text,
+      &mut self.border,
+      &mut self.title,
+      &mut self.greet,
+      &mut self.prompt,
+      &mut self.input,
+      &mut self.action,
+      &mut self.button,
+      &mut self.selection,
+      &mut self.error,
+      &mut self.caps,
+    ];
+
+    for spec in specs.iter_mut().filter_map(|spec| spec.as_mut()) {
+      let style = &mut spec.style;
 
-    style
+      style.fg = style.fg.and_then(|color| depth.downgrade(color, false));
+      style.bg = style.bg.and_then(|color| depth.downgrade(color, true));
+
+      if depth == ColorDepth::Monochrome {
+        style.add_modifier &= Modifier::BOLD | Modifier::REVERSED;
+        style.sub_modifier &= Modifier::BOLD | Modifier::REVERSED;
+      }
+    }
+
+    if depth == ColorDepth::Monochrome {
+      for spec in [&mut self.selection, &mut self.caps].iter_mut().filter_map(|spec| spec.as_mut()) {
+        spec.style = spec.style.add_modifier(Modifier::REVERSED);
+      }
+
+      let error = self.error.get_or_insert_with(Spec::default);
+      error.style = error.style.add_modifier(Modifier::BOLD);
+    }
+  }
+
+  pub fn problems(&self) -> &[String] {
+    &self.problems
   }
 
   pub fn of(&self, targets: &[Themed]) -> Style {
     targets.iter().fold(Style::default(), |style, target| self.apply(style, target))
   }
 
-  fn apply(&self, style: Style, target: &Themed) -> Style {
+  // Type of the borders drawn around an element, if one was configured.
+  pub fn border_type(&self, target: Themed, default: BorderType) -> BorderType {
+    self.spec(&target).and_then(|spec| spec.border).unwrap_or(default)
+  }
+
+  fn spec(&self, target: &Themed) -> Option<&Spec> {
     use Themed::*;
 
-    let color = match target {
+    let spec = match target {
       Container => &self.container,
+      Menu => &self.menu,
       Time => &self.time,
+      Clock => &self.clock,
+      Date => &self.date,
       Text => &self.text,
       Border => &self.border,
       Title => &self.title,
@@ -94,15 +292,239 @@ impl Theme {
       Input => &self.input,
       Action => &self.action,
       ActionButton => &self.button,
+      Selection => &self.selection,
+      Error => &self.error,
+      Caps => &self.caps,
+    };
+
+    spec.as_ref()
+  }
+
+  fn apply(&self, style: Style, target: &Themed) -> Style {
+    match (self.spec(target), target) {
+      (Some(spec), _) => style.patch(spec.style),
+
+      // Selections and badges stand out with reversed colors, unless they were
+      // themed.
+      (None, Themed::Selection) => style.add_modifier(Modifier::REVERSED),
+      (None, Themed::Caps) => self.apply(style, &Themed::ActionButton).add_modifier(Modifier::REVERSED),
+
+      (None, _) => style,
+    }
+  }
+}
+
+// Reads a named theme from the first theme directory that has it, or from the
+// built-in themes. A path to a theme file can also be given.
+fn read_theme(name: &str) -> Result<toml::Table, Box<dyn Error>> {
+  let path = match name.contains('/') {
+    true => Some(Path::new(name).to_path_buf()),
+    false => THEME_DIRS.iter().map(|dir| Path::new(dir).join(format!("{name}.toml"))).find(|path| path.exists()),
+  };
+
+  let (origin, content) = match path {
+    Some(path) => {
+      let content = fs::read_to_string(&path).map_err(|err| format!("{}: {err}", path.display()))?;
+
+      (path.display().to_string(), content)
+    }
+
+    None => match BuiltinThemes::get(&format!("{name}.toml")) {
+      Some(file) => (format!("built-in theme '{name}'"), String::from_utf8_lossy(&file.data).to_string()),
+      None => return Err(format!("unknown theme '{name}', built-in themes are {}", builtin_themes().join(", ")).into()),
+    },
+  };
+
+  toml::from_str(&content).map_err(|err| format!("{origin}: {}", err.message()).into())
+}
+
+pub fn builtin_themes() -> Vec<String> {
+  let mut names: Vec<String> = BuiltinThemes::iter()
+    .filter_map(|file| file.strip_suffix(".toml").map(str::to_string))
+    .collect();
+
+  names.sort();
+  names
+}
+
+fn parse_spec(value: &str, component: Component, bordered: bool) -> Result<Spec, String> {
+  let mut spec = Spec::default();
+
+  for token in value.split(',').map(str::trim) {
+    let modifier = match token {
+      "bold" => Some(Modifier::BOLD),
+      "italic" => Some(Modifier::ITALIC),
+      "dim" => Some(Modifier::DIM),
+      "underline" => Some(Modifier::UNDERLINED),
+      "reversed" => Some(Modifier::REVERSED),
+      _ => None,
+    };
+
+    let border = match token {
+      "plain" => Some(BorderType::Plain),
+      "rounded" => Some(BorderType::Rounded),
+      "double" => Some(BorderType::Double),
+      "thick" => Some(BorderType::Thick),
+      _ => None,
     };
 
-    match color {
-      Some((component, color)) => match component {
-        Component::Fg => style.fg(*color),
-        Component::Bg => style.bg(*color),
-      },
+    if let Some(modifier) = modifier {
+      spec.style = spec.style.add_modifier(modifier);
+    } else if let Some(border) = border {
+      if !bordered {
+        return Err(format!("border type '{token}' cannot be used for this element"));
+      }
+
+      spec.border = Some(border);
+    } else {
+      let (component, color) = match token.split_once(':') {
+        Some(("fg", color)) => (Component::Fg, color),
+        Some(("bg", color)) => (Component::Bg, color),
+        _ => (component, token),
+      };
+
+      let Ok(color) = Color::from_str(color) else {
+        return Err(format!("unknown color or modifier '{token}'"));
+      };
 
-      None => style,
+      spec.style = match component {
+        Component::Fg => spec.style.fg(color),
+        Component::Bg => spec.style.bg(color),
+      };
     }
   }
+
+  Ok(spec)
+}
+
+#[cfg(test)]
+mod tests {
+  use tui::{
+    style::{Color, Modifier, Style},
+    widgets::BorderType,
+  };
+
+  use std::{env, fs, process};
+
+  use super::{builtin_themes, ColorDepth, Theme, Themed};
+
+  #[test]
+  fn plain_colors_keep_their_component() {
+    let theme = Theme::load("container=black;border=magenta;text=cyan").unwrap();
+
+    assert!(theme.problems().is_empty());
+    assert_eq!(theme.of(&[Themed::Container]), Style::default().bg(Color::Black));
+    assert_eq!(theme.of(&[Themed::Border]), Style::default().fg(Color::Magenta));
+    assert_eq!(theme.of(&[Themed::Title]), Style::default().fg(Color::Magenta));
+    assert_eq!(theme.of(&[Themed::Clock]), Style::default().fg(Color::Cyan));
+  }
+
+  #[test]
+  fn full_style_specs() {
+    let theme = Theme::load("prompt=fg:green,bg:black,bold,underline;title=bold,italic;menu=bg:blue,double").unwrap();
+
+    assert!(theme.problems().is_empty());
+
+    assert_eq!(
+      theme.of(&[Themed::Prompt]),
+      Style::default()
+        .fg(Color::Green)
+        .bg(Color::Black)
+        .add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
+    );
+    assert_eq!(theme.of(&[Themed::Title]), Style::default().add_modifier(Modifier::BOLD | Modifier::ITALIC));
+    assert_eq!(theme.of(&[Themed::Menu]), Style::default().bg(Color::Blue));
+    assert_eq!(theme.border_type(Themed::Menu, BorderType::Plain), BorderType::Double);
+    assert_eq!(theme.border_type(Themed::Container, BorderType::Rounded), BorderType::Rounded);
+  }
+
+  #[test]
+  fn selection_and_badges_are_reversed_by_default() {
+    let theme = Theme::load("button=yellow").unwrap();
+
+    assert_eq!(theme.of(&[Themed::Selection]), Style::default().add_modifier(Modifier::REVERSED));
+    assert_eq!(
+      theme.of(&[Themed::Caps]),
+      Style::default().fg(Color::Yellow).add_modifier(Modifier::REVERSED)
+    );
+
+    let theme = Theme::load("selection=bg:blue;caps=fg:red,bold").unwrap();
+
+    assert_eq!(theme.of(&[Themed::Selection]), Style::default().bg(Color::Blue));
+    assert_eq!(theme.of(&[Themed::Caps]), Style::default().fg(Color::Red).add_modifier(Modifier::BOLD));
+  }
+
+  #[test]
+  fn monochrome_keeps_only_bold_and_reverse() {
+    let mut theme = Theme::load("text=#88c0d0,italic;selection=bg:blue;caps=fg:red,bold;input=underline,bold").unwrap();
+    theme.downgrade(ColorDepth::Monochrome);
+
+    assert_eq!(theme.of(&[Themed::Text]), Style::default());
+    assert_eq!(theme.of(&[Themed::Input]), Style::default().add_modifier(Modifier::BOLD));
+    assert_eq!(theme.of(&[Themed::Selection]), Style::default().add_modifier(Modifier::REVERSED));
+    assert_eq!(
+      theme.of(&[Themed::Caps]),
+      Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED)
+    );
+    assert_eq!(theme.of(&[Themed::Error]), Style::default().add_modifier(Modifier::BOLD));
+  }
+
+  #[test]
+  fn reports_problems() {
+    let theme = Theme::load("prompt=green,blinking;title=double;foo=red;border").unwrap();
+
+    assert_eq!(
+      theme.problems(),
+      [
+        "invalid value 'green,blinking' for key 'prompt': unknown color or modifier 'blinking'",
+        "invalid value 'double' for key 'title': border type 'double' cannot be used for this element",
+        "unknown key 'foo'",
+        "malformed directive 'border'",
+      ]
+    );
+  }
+
+  #[test]
+  fn builtin_themes_are_valid() {
+    for name in builtin_themes() {
+      let theme = Theme::load(&name).unwrap();
+
+      assert!(theme.problems().is_empty(), "{}: {:?}", name, theme.problems());
+    }
+
+    assert!(Theme::load("does-not-exist").is_err());
+  }
+
+  #[test]
+  fn inline_specs_inherit_from_named_themes() {
+    let theme = Theme::load("prompt=red;inherits=high-contrast").unwrap();
+
+    assert_eq!(theme.of(&[Themed::Prompt]), Style::default().fg(Color::Red));
+    assert_eq!(theme.border_type(Themed::Container, BorderType::Rounded), BorderType::Double);
+  }
+
+  #[test]
+  fn theme_files_inherit_from_each_other() {
+    let dir = env::temp_dir().join(format!("tuigreet-themes-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let (base, child, cycle) = (dir.join("base.toml"), dir.join("child.toml"), dir.join("cycle.toml"));
+
+    fs::write(&base, "prompt = \"green\"\ninput = \"yellow,bold\"\n").unwrap();
+    fs::write(&child, format!("inherits = \"{}\"\nprompt = \"blue\"\nwidth = 3\n", base.display())).unwrap();
+    fs::write(&cycle, format!("inherits = \"{}\"\n", cycle.display())).unwrap();
+
+    let theme = Theme::load(&child.display().to_string()).unwrap();
+
+    assert_eq!(theme.of(&[Themed::Prompt]), Style::default().fg(Color::Blue));
+    assert_eq!(theme.of(&[Themed::Input]), Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD));
+    assert_eq!(
+      theme.problems(),
+      [format!("theme '{}': invalid value '3' for key 'width': expected a string", child.display())]
+    );
+
+    assert!(Theme::load(&cycle.display().to_string()).is_err());
+
+    fs::remove_dir_all(dir).unwrap();
+  }
 }
@@ -1,12 +1,15 @@
+pub mod clock;
 mod command;
 pub mod common;
 mod i18n;
 pub mod power;
 mod processing;
 mod prompt;
+pub mod screen;
 pub mod sessions;
 pub mod users;
 mod util;
+pub mod widgets;
 
 use std::{
   error::Error,
@@ -17,20 +20,25 @@ use std::{
 use sessions::SessionSource;
 use tokio::sync::RwLock;
 use tui::{
-  layout::{Alignment, Constraint, Direction, Layout},
-  style::Modifier,
+  layout::{Alignment, Constraint, Direction, Layout, Rect},
+  style::{Modifier, Style},
   text::{Line, Span},
   widgets::Paragraph,
   Frame as CrosstermFrame, Terminal,
 };
+use screen::{Regions, Screen};
 use util::buttonize;
+use widgets::{Region, Widget};
 
-use crate::{info::capslock_status, ui::util::should_hide_cursor, Greeter, Mode};
+use crate::{
+  keymap::Action,
+  ui::util::{display_width, should_hide_cursor},
+  Greeter, Mode,
+};
 
 use self::common::style::{Theme, Themed};
 pub use self::i18n::MESSAGES;
 
-const STATUSBAR_INDEX: usize = 2;
 const STATUSBAR_LEFT_INDEX: usize = 1;
 const STATUSBAR_RIGHT_INDEX: usize = 2;
 
@@ -56,91 +64,31 @@ where
   }
 
   terminal.draw(|f| {
-    let theme = &greeter.theme;
-
-    let size = f.size();
-    let chunks = Layout::default()
-      .constraints(
-        [
-          Constraint::Length(greeter.window_padding()), // Top vertical padding
-          Constraint::Min(1),                           // Main area
-          Constraint::Length(1),                        // Status line
-          Constraint::Length(greeter.window_padding()), // Bottom vertical padding
-        ]
-        .as_ref(),
-      )
-      .split(size);
-
-    let session_source_label = match greeter.session_source {
-      SessionSource::Session(_) => fl!("status_session"),
-      _ => fl!("status_command"),
-    };
-    let session_source = greeter.session_source.label(&greeter).unwrap_or("-");
-
-    let status_block_size_right = session_source_label.chars().count() as u16
-      + 1
-      + session_source.chars().count() as u16
-      + 1
-      + fl!("status_caps").chars().count() as u16
-      + greeter.window_padding();
-    let status_block_size_left = (size.width - greeter.window_padding()) - status_block_size_right;
-
-    let status_chunks = Layout::default()
-      .direction(Direction::Horizontal)
-      .constraints(
-        [
-          Constraint::Length(greeter.window_padding()),
-          Constraint::Length(status_block_size_left),
-          Constraint::Length(status_block_size_right),
-          Constraint::Length(greeter.window_padding()),
-        ]
-        .as_ref(),
-      )
-      .split(chunks[STATUSBAR_INDEX]);
-
-    let status_left_text = Line::from(vec![
-      status_label(theme, format!("F{}", greeter.kb_command)),
-      status_value(&greeter, theme, Button::Command, fl!("action_command")),
-      Span::from(" "),
-      status_label(theme, format!("F{}", greeter.kb_sessions)),
-      status_value(&greeter, theme, Button::Session, fl!("action_session")),
-      Span::from(" "),
-      status_label(theme, format!("F{}", greeter.kb_fortune)),
-      status_value(&greeter, theme, Button::Command, "Fortune"),
-      Span::from(" "),
-      status_label(theme, format!("F{}", greeter.kb_power)),
-      status_value(&greeter, theme, Button::Power, fl!("action_power")),
-    ]);
-    let status_left = Paragraph::new(status_left_text);
+    let screen = Screen::new(&mut greeter, f.area());
 
-    f.render_widget(status_left, status_chunks[STATUSBAR_LEFT_INDEX]);
-
-    let status_right_text = Line::from(
-      vec![
-        status_label(theme, session_source_label),
-        status_value(&greeter, theme, Button::Other, session_source),
-        Span::from(" "),
-      ]
-      .into_iter()
-      .chain(capslock_status().then(|| status_label(theme, fl!("status_caps"))))
-      .collect::<Vec<_>>(),
-    );
-    let status_right = Paragraph::new(status_right_text).alignment(Alignment::Right);
+    greeter.regions = Regions::default();
 
-    f.render_widget(status_right, status_chunks[STATUSBAR_RIGHT_INDEX]);
+    // Widgets at the edges of the screen are displayed in every mode, while
+    // those around the prompt are drawn with it.
+    for placed in screen.widgets(Region::Top).chain(screen.widgets(Region::Bottom)) {
+      match placed.widget {
+        Widget::Status => greeter.regions.buttons = draw_status(&greeter, f, placed.area),
+        _ => widgets::draw(&greeter, f, placed),
+      }
+    }
 
     let cursor = match greeter.mode {
-      Mode::Command => self::command::draw(&mut greeter, f).ok(),
-      Mode::Sessions => greeter.sessions.draw(&greeter, f).ok(),
-      Mode::Power => greeter.powers.draw(&greeter, f).ok(),
-      Mode::Users => greeter.users.draw(&greeter, f).ok(),
-      Mode::Processing => self::processing::draw(&mut greeter, f).ok(),
-      _ => self::prompt::draw(&mut greeter, f).ok(),
+      Mode::Command => self::command::draw(&mut greeter, f, screen.main).ok(),
+      Mode::Sessions => greeter.sessions.draw(&greeter, f, screen.main).ok(),
+      Mode::Power => greeter.powers.draw(&greeter, f, screen.main).ok(),
+      Mode::Users => greeter.users.draw(&greeter, f, screen.main).ok(),
+      Mode::Processing => self::processing::draw(&mut greeter, f, screen.main).ok(),
+      _ => self::prompt::draw(&mut greeter, f, &screen).ok(),
     };
 
     if !hide_cursor {
       if let Some(cursor) = cursor {
-        f.set_cursor(cursor.0 - 1, cursor.1 - 1);
+        f.set_cursor_position((cursor.0.saturating_sub(1), cursor.1.saturating_sub(1)));
       }
     }
   })?;
@@ -150,6 +98,129 @@ where
   Ok(())
 }
 
+// Draws the status bar, with the key hints on the left, and the state of the
+// session, keyboard layout and locks on the right. Returns where each hint was
+// drawn, so it can be clicked.
+fn draw_status(greeter: &Greeter, f: &mut Frame, area: Rect) -> Vec<(Rect, Action)> {
+  let theme = &greeter.settings.theme;
+
+  let session_source_label = match greeter.session_source {
+    SessionSource::Session(_) => fl!("status_session"),
+    _ => fl!("status_command"),
+  };
+  let session_source = greeter.session_source.label(greeter).unwrap_or("-");
+
+  // Caps Lock can be themed on its own, since it matters when typing secrets.
+  let badge = theme.of(&[Themed::ActionButton]).add_modifier(Modifier::REVERSED);
+  let locks: Vec<(String, Style)> = vec![
+    (greeter.locks.caps, fl!("status_caps"), theme.of(&[Themed::Caps])),
+    (greeter.locks.num, fl!("status_num"), badge),
+    (greeter.locks.scroll, fl!("status_scroll"), badge),
+  ]
+  .into_iter()
+  .filter_map(|(enabled, label, style)| enabled.then_some((label, style)))
+  .collect();
+
+  let layout = greeter.layout.as_deref().map(|layout| (fl!("status_layout"), layout));
+
+  let status_block_size_right = layout
+    .as_ref()
+    .map(|(label, layout)| display_width(label) + 1 + display_width(layout) + 1)
+    .unwrap_or(0)
+    + display_width(&session_source_label)
+    + 1
+    + display_width(session_source)
+    + 1
+    + locks.iter().map(|(label, _)| display_width(label) + 1).sum::<u16>()
+    + greeter.settings.window_padding;
+  let status_block_size_left = area.width.saturating_sub(greeter.settings.window_padding).saturating_sub(status_block_size_right);
+
+  let status_chunks = Layout::default()
+    .direction(Direction::Horizontal)
+    .constraints(
+      [
+        Constraint::Length(greeter.settings.window_padding),
+        Constraint::Length(status_block_size_left),
+        Constraint::Length(status_block_size_right),
+        Constraint::Length(greeter.settings.window_padding),
+      ]
+      .as_ref(),
+    )
+    .split(area);
+
+  let hints = [
+    (Action::OpenCommand, Button::Command, fl!("action_command")),
+    (Action::OpenSessions, Button::Session, fl!("action_session")),
+    (Action::RefreshFortune, Button::Other, "Fortune".to_string()),
+    (Action::SwitchLayout, Button::Other, fl!("action_layout")),
+    (Action::OpenPower, Button::Power, fl!("action_power")),
+  ];
+
+  let mut status_left_spans = Vec::new();
+  let mut buttons = Vec::new();
+  let mut button_x = status_chunks[STATUSBAR_LEFT_INDEX].x;
+
+  for (action, button, text) in hints {
+    if let Some(chord) = greeter.settings.keymap.hint(action) {
+      if !status_left_spans.is_empty() {
+        status_left_spans.push(Span::from(" "));
+        button_x = button_x.saturating_add(1);
+      }
+
+      status_left_spans.push(status_label(theme, chord));
+      status_left_spans.push(status_value(greeter, theme, button, text));
+
+      let button_width = status_left_spans[status_left_spans.len() - 2..]
+        .iter()
+        .map(|span| display_width(&span.content))
+        .sum();
+      let button_rect = Rect::new(button_x, area.y, button_width, 1).intersection(status_chunks[STATUSBAR_LEFT_INDEX]);
+
+      if !button_rect.is_empty() {
+        buttons.push((button_rect, action));
+      }
+
+      button_x = button_x.saturating_add(button_width);
+    }
+  }
+
+  let status_left_text = Line::from(status_left_spans);
+  let status_left = Paragraph::new(status_left_text);
+
+  f.render_widget(status_left, status_chunks[STATUSBAR_LEFT_INDEX]);
+
+  let mut status_right_spans = Vec::new();
+
+  if let Some((label, layout)) = layout {
+    status_right_spans.extend([
+      status_label(theme, label),
+      status_value(greeter, theme, Button::Other, layout),
+      Span::from(" "),
+    ]);
+  }
+
+  status_right_spans.extend([
+    status_label(theme, session_source_label),
+    status_value(greeter, theme, Button::Other, session_source),
+    Span::from(" "),
+  ]);
+
+  for (index, (label, style)) in locks.into_iter().enumerate() {
+    if index > 0 {
+      status_right_spans.push(Span::from(" "));
+    }
+
+    status_right_spans.push(Span::styled(label, style));
+  }
+
+  let status_right_text = Line::from(status_right_spans);
+  let status_right = Paragraph::new(status_right_text).alignment(Alignment::Right);
+
+  f.render_widget(status_right, status_chunks[STATUSBAR_RIGHT_INDEX]);
+
+  buttons
+}
+
 fn status_label<'s, S>(theme: &Theme, text: S) -> Span<'s>
 where
   S: Into<String>,
@@ -191,3 +262,71 @@ where
     None => Span::from(""),
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+
+  use tokio::sync::RwLock;
+  use tui::{backend::TestBackend, Terminal};
+
+  use crate::{
+    ui::{power::Power, sessions::Session, users::User},
+    Greeter, Mode,
+  };
+
+  fn greeter(mode: Mode, window_padding: u16) -> Greeter {
+    let mut greeter = Greeter::default();
+
+    greeter.mode = mode;
+    greeter.settings.window_padding = window_padding;
+    greeter.prompt = Some("Password:".to_string());
+    greeter.message = Some("Authentication failed, please try again".to_string());
+    greeter.fortune = "A fortune long enough to be wrapped on narrow terminals.\n--- Someone".to_string();
+    greeter.layout = Some("fr-latin9".to_string());
+    greeter.locks.caps = true;
+
+    greeter.username.value.set("山田太郎".to_string());
+    greeter.users.options = vec![User {
+      username: "apognu".to_string(),
+      name: Some("Antoine POPINEAU".to_string()),
+    }];
+    greeter.sessions.options = vec![Session {
+      name: "Sway".to_string(),
+      command: "sway".to_string(),
+      ..Default::default()
+    }];
+    greeter.powers.options = vec![Power {
+      label: "Shut down".to_string(),
+      ..Default::default()
+    }];
+
+    greeter
+  }
+
+  #[tokio::test]
+  async fn draws_down_to_tiny_terminals() {
+    let modes = [
+      Mode::Username,
+      Mode::Password,
+      Mode::Command,
+      Mode::Users,
+      Mode::Sessions,
+      Mode::Power,
+      Mode::Processing,
+    ];
+
+    for mode in modes {
+      for window_padding in [0, 2] {
+        for width in [20, 21, 25, 30, 40, 60, 80, 120] {
+          for height in (5..=20).chain([30, 50]) {
+            let greeter = Arc::new(RwLock::new(greeter(mode, window_padding)));
+            let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+
+            super::draw(greeter, &mut terminal).await.unwrap();
+          }
+        }
+      }
+    }
+  }
+}
@@ -7,23 +7,26 @@ use tui::{
 };
 
 use crate::{
-  ui::{util::*, Frame},
+  ui::{common::style::Themed, util::*, Frame},
   Greeter,
 };
 
-pub fn draw(greeter: &mut Greeter, f: &mut Frame) -> Result<(u16, u16), Box<dyn Error>> {
-  let size = f.size();
-
-  let width = greeter.width();
-  let height: u16 = get_height(greeter) + 1;
-  let x = (size.width - width) / 2;
-  let y = (size.height - height) / 2;
+pub fn draw(greeter: &mut Greeter, f: &mut Frame, area: Rect) -> Result<(u16, u16), Box<dyn Error>> {
+  let (x, y, width, height) = get_rect_bounds(greeter, area, 1);
 
   let container = Rect::new(x, y, width, height);
-  let container_padding = greeter.container_padding();
-  let frame = Rect::new(x + container_padding, y + container_padding, width - (2 * container_padding), height - (2 * container_padding));
-
-  let block = Block::default().borders(Borders::ALL).border_type(BorderType::Plain);
+  let container_padding = greeter.settings.container_padding;
+  let frame = Rect::new(
+    x + container_padding,
+    y + container_padding,
+    width.saturating_sub(2 * container_padding),
+    height.saturating_sub(2 * container_padding),
+  );
+
+  let theme = &greeter.settings.theme;
+  let block = Block::default()
+    .borders(Borders::ALL)
+    .border_type(theme.border_type(Themed::Container, BorderType::Plain));
 
   let constraints = [Constraint::Length(1)];
 
@@ -0,0 +1,276 @@
+use tui::{
+  layout::{Alignment, Rect},
+  text::Text,
+  widgets::{Paragraph, Wrap},
+};
+
+use crate::{
+  keymap::Action,
+  ui::{
+    util::{get_height, get_rect_bounds},
+    widgets::{self, Region, Widget},
+  },
+  Greeter,
+};
+
+// Maximum width text widgets are wrapped at.
+const MAX_WIDGET_WIDTH: u16 = 120;
+
+// Regions, in the order they are dropped when the terminal is too small.
+const DROP_ORDER: [Region; 4] = [Region::AbovePrompt, Region::BelowPrompt, Region::Top, Region::Bottom];
+
+// Regions of the screen, computed from the size of the terminal, the layout and
+// the content to display.
+//
+// Widgets of the top and bottom regions are stacked against the edges of the
+// screen, inside the window padding, and those around the prompt against it,
+// separated by blank lines. When the terminal is too small to display
+// everything, whole regions are dropped in steps: first the one above the
+// prompt, then the one below it, then the top and the bottom ones. The prompt
+// is centered in the remaining space, but moved up or down if it would overlap
+// the widgets around it.
+pub struct Screen {
+  // Area the prompt and the menus are centered in.
+  pub main: Rect,
+  // Position of the prompt container.
+  pub prompt: Rect,
+  // Widgets that fit on the screen.
+  pub widgets: Vec<Placed>,
+}
+
+// Widget, its content and where it is drawn.
+pub struct Placed {
+  pub region: Region,
+  pub widget: Widget,
+  pub area: Rect,
+  // Text of the widget, computed once per frame. The status bar draws itself.
+  pub text: Option<Text<'static>>,
+}
+
+// Widget of a region, before it is positioned.
+struct Sized {
+  widget: Widget,
+  alignment: Alignment,
+  width: u16,
+  height: u16,
+  text: Option<Text<'static>>,
+}
+
+// Areas of the last drawn screen that respond to mouse clicks. Menus keep track
+// of where their own options are drawn.
+#[derive(Default)]
+pub struct Regions {
+  // Status bar buttons, and the action they trigger.
+  pub buttons: Vec<(Rect, Action)>,
+  // Username line of the prompt, when it is displayed. The answer line is only
+  // displayed while it has the focus, so it needs no region.
+  pub username: Option<Rect>,
+}
+
+impl Screen {
+  pub fn new(greeter: &mut Greeter, area: Rect) -> Screen {
+    let padding = greeter.settings.window_padding;
+    let inner = Rect::new(
+      area.x + padding.min(area.width / 2),
+      area.y,
+      area.width.saturating_sub(padding.saturating_mul(2)),
+      area.height,
+    );
+    let text_width = inner.width.min(MAX_WIDGET_WIDTH);
+
+    let layout = greeter.settings.widgets.clone();
+    let mut regions: Vec<(Region, Vec<Sized>)> = [Region::Top, Region::AbovePrompt, Region::BelowPrompt, Region::Bottom]
+      .iter()
+      .map(|region| {
+        let widgets = layout
+          .region(*region)
+          .iter()
+          .filter_map(|placement| {
+            if placement.widget == Widget::Status {
+              return Some(Sized {
+                widget: Widget::Status,
+                alignment: placement.alignment,
+                width: area.width,
+                height: 1,
+                text: None,
+              });
+            }
+
+            let text = widgets::content(greeter, placement.widget, text_width, area.height)?;
+            let mut paragraph = Paragraph::new(text.clone());
+
+            if widgets::wraps(placement.widget) {
+              paragraph = paragraph.wrap(Wrap { trim: false });
+            }
+
+            Some(Sized {
+              widget: placement.widget,
+              alignment: placement.alignment,
+              width: (paragraph.line_width() as u16).min(text_width),
+              height: paragraph.line_count(text_width).min(u16::MAX as usize) as u16,
+              text: Some(text),
+            })
+          })
+          .collect();
+
+        (*region, widgets)
+      })
+      .collect();
+
+    let prompt_height = get_height(greeter);
+
+    for region in DROP_ORDER {
+      if needed(&regions, padding, prompt_height) <= area.height as u32 {
+        break;
+      }
+
+      regions.retain(|(other, _)| *other != region);
+    }
+
+    let heights: Vec<(Region, u16)> = regions
+      .iter()
+      .map(|(region, widgets)| (*region, stacked_height(*region, widgets) as u16))
+      .collect();
+    let height = |region: Region| heights.iter().find(|(other, _)| *other == region).map_or(0, |(_, height)| *height);
+
+    let edge = match height(Region::Top) + height(Region::Bottom) {
+      0 => 0,
+      _ => padding,
+    };
+
+    let main = {
+      let y = area.y + edge + height(Region::Top);
+      let bottom = area.bottom() - edge - height(Region::Bottom);
+
+      Rect::new(area.x, y, area.width, bottom.saturating_sub(y))
+    };
+
+    let (x, y, width, prompt_height) = get_rect_bounds(greeter, main, 0);
+
+    let top = main.y + height(Region::AbovePrompt);
+    let bottom = main.bottom() - height(Region::BelowPrompt);
+    let y = y.max(top).min(bottom.saturating_sub(prompt_height)).max(main.y);
+
+    let prompt = Rect::new(x, y, width, prompt_height);
+
+    let mut placed = Vec::new();
+
+    for (region, widgets) in regions {
+      let mut y = match region {
+        Region::Top => area.y + edge,
+        Region::AbovePrompt => prompt.y.saturating_sub(height(Region::AbovePrompt)),
+        Region::BelowPrompt => prompt.bottom() + 1,
+        Region::Bottom => main.bottom(),
+      };
+
+      for sized in widgets {
+        let area = match sized.widget {
+          Widget::Status => Rect::new(area.x, y, area.width, 1),
+          _ => widgets::align(Rect::new(inner.x, y, inner.width, sized.height), sized.width, sized.alignment),
+        };
+
+        placed.push(Placed {
+          region,
+          widget: sized.widget,
+          area,
+          text: sized.text,
+        });
+
+        y += sized.height + 1;
+      }
+    }
+
+    Screen {
+      main,
+      prompt,
+      widgets: placed,
+    }
+  }
+
+  // Widgets of a region that fit on the screen.
+  pub fn widgets(&self, region: Region) -> impl Iterator<Item = &Placed> {
+    self.widgets.iter().filter(move |placed| placed.region == region)
+  }
+}
+
+// Height taken by the widgets of a region, separated by blank lines. Widgets
+// below the prompt are also separated from it.
+fn stacked_height(region: Region, widgets: &[Sized]) -> u32 {
+  if widgets.is_empty() {
+    return 0;
+  }
+
+  let gaps = widgets.len() as u32 - 1 + if region == Region::BelowPrompt { 1 } else { 0 };
+
+  widgets.iter().map(|sized| sized.height as u32).sum::<u32>() + gaps
+}
+
+// Number of lines needed to display the prompt and some regions.
+fn needed(regions: &[(Region, Vec<Sized>)], padding: u16, prompt_height: u16) -> u32 {
+  let edges = regions
+    .iter()
+    .any(|(region, widgets)| matches!(region, Region::Top | Region::Bottom) && !widgets.is_empty());
+
+  let regions: u32 = regions.iter().map(|(region, widgets)| stacked_height(*region, widgets)).sum();
+
+  prompt_height as u32 + regions + if edges { 2 * padding as u32 } else { 0 }
+}
+
+#[cfg(test)]
+mod tests {
+  use tui::layout::Rect;
+
+  use super::Screen;
+  use crate::{
+    ui::widgets::{Region, Widget},
+    Greeter,
+  };
+
+  #[test]
+  fn regions_are_dropped_in_order() {
+    let mut greeter = Greeter::default();
+    greeter.fortune = "Some fortune\nwritten on\nthree lines".to_string();
+    greeter.hostname = "localhost".to_string();
+    greeter.settings.window_padding = 1;
+    greeter.settings.widgets.set("top=hostname:left").unwrap();
+
+    let regions = [Region::AbovePrompt, Region::BelowPrompt, Region::Top, Region::Bottom];
+    let mut previous = [true; 4];
+
+    for height in (3..=40).rev() {
+      let screen = Screen::new(&mut greeter, Rect::new(0, 0, 80, height));
+      let visible = regions.map(|region| screen.widgets(region).next().is_some());
+
+      // Regions go in order, and nothing comes back as the terminal gets
+      // smaller.
+      for index in 1..regions.len() {
+        assert!(!visible[index - 1] || visible[index], "{:?} at height {}", visible, height);
+      }
+
+      for index in 0..regions.len() {
+        assert!(visible[index] <= previous[index]);
+      }
+
+      previous = visible;
+
+      for placed in &screen.widgets {
+        match placed.region {
+          Region::AbovePrompt => assert!(placed.area.bottom() <= screen.prompt.y),
+          Region::BelowPrompt => assert!(placed.area.y > screen.prompt.bottom()),
+          Region::Top => assert!(placed.area.bottom() <= screen.main.y),
+          Region::Bottom => assert!(placed.area.y >= screen.main.bottom()),
+        }
+
+        assert!(placed.area.bottom() <= height);
+      }
+
+      if let Some(status) = screen.widgets(Region::Bottom).find(|placed| placed.widget == Widget::Status) {
+        assert_eq!(status.area, Rect::new(0, height - 2, 80, 1));
+      }
+
+      assert!(screen.prompt.bottom() <= height);
+    }
+
+    assert_eq!(previous, [false; 4]);
+  }
+}
@@ -58,7 +58,7 @@ impl SessionSource {
 }
 
 // Represents the XDG type of the selected session.
-#[derive(SmartDefault, Debug, Copy, Clone, PartialEq)]
+#[derive(SmartDefault, Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum SessionType {
   X11,
   Wayland,
@@ -88,7 +88,10 @@ pub struct Session {
   pub slug: Option<String>,
   // Human-friendly name for the session, maps to the `Name` attribute.
   pub name: String,
-  // Command used to start the session, maps to the `Exec` attribute.
+  // Description of the session, maps to the `Comment` attribute.
+  pub comment: Option<String>,
+  // Command used to start the session, from the `Exec` attribute, quoted for
+  // the shell.
   pub command: String,
   // XDG session type for the session, detected from the location of the session
   // file.
@@ -104,6 +107,10 @@ impl MenuItem for Session {
   fn format(&self) -> Cow<'_, str> {
     Cow::Borrowed(&self.name)
   }
+
+  fn subtitle(&self) -> Option<Cow<'_, str>> {
+    self.comment.as_deref().map(Cow::Borrowed)
+  }
 }
 
 impl Session {
@@ -1,14 +1,23 @@
-use std::borrow::Cow;
-
-use ansi_to_tui::IntoText;
-use chrono::Local;
 use tui::{
   prelude::Rect,
-  text::Text,
   widgets::{Paragraph, Wrap},
 };
+use unicode_width::UnicodeWidthStr;
+
+use crate::{Greeter, Mode};
+
+// Number of terminal columns taken by a text, which differs from its number of
+// characters for wide (like CJK) and zero-width (like combining) characters.
+pub fn display_width(text: &str) -> u16 {
+  text.width().min(u16::MAX as usize) as u16
+}
+
+// Pads a text with spaces so it takes at least the given number of columns.
+pub fn pad_to_width(text: &str, width: u16) -> String {
+  let padding = width.saturating_sub(display_width(text)) as usize;
 
-use crate::{fortune::get_figlet, Greeter, Mode};
+  format!("{text}{}", " ".repeat(padding))
+}
 
 pub fn titleize(message: &str) -> String {
   format!(" {message} ")
@@ -24,7 +33,7 @@ pub fn buttonize(message: &str) -> String {
 pub fn should_hide_cursor(greeter: &Greeter) -> bool {
   greeter.working
     || greeter.done
-    || (greeter.user_menu && greeter.mode == Mode::Username && greeter.username.value.is_empty())
+    || (greeter.settings.user_menu && greeter.mode == Mode::Username && greeter.username.value.is_empty())
     || (greeter.mode == Mode::Password && greeter.prompt.is_none())
     || greeter.mode == Mode::Users
     || greeter.mode == Mode::Sessions
@@ -45,8 +54,8 @@ pub fn should_hide_cursor(greeter: &Greeter) -> bool {
 // |                        | <- container padding
 // +------------------------+
 pub fn get_height(greeter: &Greeter) -> u16 {
-  let container_padding = greeter.container_padding();
-  let prompt_padding = greeter.prompt_padding();
+  let container_padding = greeter.settings.container_padding;
+  let prompt_padding = greeter.settings.prompt_padding;
 
   match greeter.mode {
     Mode::Username | Mode::Action | Mode::Command => (2 * container_padding) + 1,
@@ -61,9 +70,15 @@ pub fn get_height(greeter: &Greeter) -> u16 {
 // Get the coordinates and size of the main window area, from the terminal size,
 // and the content we need to display.
 pub fn get_rect_bounds(greeter: &Greeter, area: Rect, items: usize) -> (u16, u16, u16, u16) {
-  let width = greeter.width();
+  let width = greeter.settings.width;
   let height: u16 = get_height(greeter) + items as u16;
 
+  center_rect(area, width, height)
+}
+
+// Centers a window of the requested size in an area of the terminal, shrinking
+// it if it does not fit.
+pub fn center_rect(area: Rect, width: u16, height: u16) -> (u16, u16, u16, u16) {
   let x = if width < area.width {
     (area.width - width) / 2
   } else {
@@ -86,76 +101,124 @@ pub fn get_rect_bounds(greeter: &Greeter, area: Rect, items: usize) -> (u16, u16
     (y, height)
   };
 
-  (x, y, width, height)
+  (area.x + x, area.y + y, width, height)
 }
 
 // Computes the size of a text entry, from the container width and, if
 // applicable, the prompt length.
 pub fn get_input_width(greeter: &Greeter, width: u16, label: &Option<String>) -> u16 {
-  let width = std::cmp::min(greeter.width(), width);
+  let width = std::cmp::min(greeter.settings.width, width);
 
   let label_width = match label {
     None => 0,
-    Some(label) => label.chars().count(),
+    Some(label) => display_width(label),
   };
 
-  width - label_width as u16 - 4 - 1
+  width.saturating_sub(label_width).saturating_sub(4 + 1)
 }
 
-pub fn get_cursor_offset(greeter: &mut Greeter, length: usize) -> i16 {
-  let mut offset = length as i16 + greeter.cursor_offset;
+pub fn get_message_height(greeter: &Greeter, padding: u16, fallback: u16) -> (Option<Paragraph<'_>>, u16) {
+  if let Some(message) = &greeter.message {
+    let width = greeter.settings.width;
+    let paragraph = Paragraph::new(message.trim_end()).wrap(Wrap { trim: true });
+    let height = paragraph.line_count(width.saturating_sub(4));
 
-  if offset < 0 {
-    offset = 0;
-    greeter.cursor_offset = -(length as i16);
+    (Some(paragraph), height as u16 + padding)
+  } else {
+    (None, fallback)
   }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs;
 
-  if offset > length as i16 {
-    offset = length as i16;
-    greeter.cursor_offset = 0;
+  use super::{display_width, get_input_width, pad_to_width};
+  use crate::{ui::common::editor::LineEditor, Greeter};
+
+  // Reads a message straight from a locale file, so every locale can be checked
+  // regardless of the one the loader picked.
+  fn message(locale: &str, id: &str) -> String {
+    let path = format!("{}/contrib/locales/{locale}/tuigreet.ftl", env!("CARGO_MANIFEST_DIR"));
+
+    fs::read_to_string(path)
+      .unwrap()
+      .lines()
+      .find_map(|line| Some(line.strip_prefix(id)?.trim_start().strip_prefix('=')?.trim().to_string()))
+      .unwrap()
   }
 
-  offset
-}
+  #[test]
+  fn cyrillic_labels_take_one_column_per_character() {
+    assert_eq!(display_width(&message("ru-RU", "username")), 17);
+    assert_eq!(display_width(&message("uk-UA", "username")), 17);
 
-pub fn get_greeting(greeter: &Greeter, area: Rect) -> Paragraph {
-  let fortune_text = match greeter.fortune.replace('\t', "    ").into_text() {
-    Ok(text) => text,
-    Err(_) => Text::raw(&greeter.fortune),
-  };
+    for locale in ["ru-RU", "uk-UA"] {
+      for id in ["username", "select_user", "wait", "failed", "action_session"] {
+        let text = message(locale, id);
 
-  let mut paragraph = Paragraph::new(fortune_text).wrap(Wrap { trim: false });
+        assert_eq!(display_width(&text) as usize, text.chars().count(), "{locale}: {id}");
+      }
+    }
+  }
+
+  #[test]
+  fn inputs_after_wide_and_combining_labels() {
+    let mut greeter = Greeter::default();
+    greeter.settings.width = 40;
+
+    // Columns, relative to the label, where the prompt layout starts and ends
+    // the input following it.
+    let bounds = |label: &str| {
+      let start = 1 + display_width(label);
+
+      (start, start + get_input_width(&greeter, 60, &Some(label.to_string())))
+    };
+
+    assert_eq!(bounds("Username:"), (10, 36));
+    assert_eq!(bounds("ユーザー名:"), (12, 36));
+    assert_eq!(bounds("사용자 이름:"), (13, 36));
+    assert_eq!(bounds("Usua\u{301}rio:"), (9, 36));
 
-  if paragraph.line_count(120) as u16 > area.height {
-    paragraph = Paragraph::new(Text::raw("Fortune too long, unfortunately"));
+    // A label wider than the prompt leaves no room for the input.
+    assert_eq!(bounds(&"名".repeat(20)), (41, 41));
   }
 
-  paragraph
-}
+  #[test]
+  fn padding_wide_and_combining_entries() {
+    assert_eq!(pad_to_width("山田太郎", 10), "山田太郎  ");
+    assert_eq!(pad_to_width("Jose\u{301}", 6), "Jose\u{301}  ");
+    assert_eq!(pad_to_width("Sway 🚀", 8), "Sway 🚀 ");
 
-pub fn get_date(greeter: &Greeter) -> Paragraph {
-  let date = Local::now()
-    .format_localized(&Cow::Owned(fl!("date")), greeter.locale)
-    .to_string();
+    // Entries and their subtitles are padded so the selection spans the whole
+    // width of the menu.
+    for (name, subtitle) in [("山田太郎", "ユーザー"), ("Jose\u{301}", "Gnome\u{301}"), ("Sway 🚀", "Wayland")] {
+      let line = format!("{name}{}", pad_to_width(&format!("  {subtitle}"), 20 - display_width(name)));
 
-  Paragraph::new(date)
-}
+      assert_eq!(display_width(&line), 20, "{}", line);
+    }
+  }
 
-pub fn get_figlet_time(greeter: &Greeter) -> Paragraph {
-  let time = Local::now().format_localized("%H:%M", greeter.locale).to_string();
-  let figlet = get_figlet(&time);
+  #[test]
+  fn prompt_width_of_wide_prompts() {
+    let mut greeter = Greeter::default();
 
-  Paragraph::new(figlet)
-}
+    greeter.prompt = Some("パスワード:".to_string());
+    assert_eq!(greeter.prompt_width(), 11);
 
-pub fn get_message_height(greeter: &Greeter, padding: u16, fallback: u16) -> (Option<Paragraph>, u16) {
-  if let Some(message) = &greeter.message {
-    let width = greeter.width();
-    let paragraph = Paragraph::new(message.trim_end()).wrap(Wrap { trim: true });
-    let height = paragraph.line_count(width - 4);
+    greeter.prompt = Some(message("uk-UA", "username"));
+    assert_eq!(greeter.prompt_width(), 17);
+  }
 
-    (Some(paragraph), height as u16 + padding)
-  } else {
-    (None, fallback)
+  #[test]
+  fn cursor_after_wide_user_names() {
+    let mut username = LineEditor::from("山田太郎".to_string());
+
+    assert_eq!(username.view(20, |_, grapheme| grapheme.to_string()), ("山田太郎".to_string(), 8));
+    assert_eq!(username.view(5, |_, grapheme| grapheme.to_string()), ("太郎".to_string(), 4));
+
+    let mut username = LineEditor::from("Jose\u{301} 🚀".to_string());
+
+    assert_eq!(username.view(20, |_, grapheme| grapheme.to_string()).1, 7);
   }
 }
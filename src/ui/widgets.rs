@@ -0,0 +1,416 @@
+use std::{borrow::Cow, fs, str::FromStr};
+
+use ansi_to_tui::IntoText;
+use chrono::Local;
+use tui::{
+  layout::{Alignment, Rect},
+  text::{Line, Text},
+  widgets::{Paragraph, Wrap},
+};
+
+use crate::{
+  ui::{common::style::Themed, screen::Placed, util::display_width, Frame},
+  Greeter, Mode,
+};
+
+// Directory text avatars are read from, as one file named after each user.
+const AVATAR_DIR: &str = "/etc/tuigreet/avatars";
+// Maximum number of lines of a text avatar.
+const AVATAR_HEIGHT: usize = 12;
+
+// Parts of the screen widgets can be displayed in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Region {
+  // Top of the screen, displayed in every mode.
+  Top,
+  // Right above the prompt, only displayed with it.
+  AbovePrompt,
+  // Right below the prompt, only displayed with it.
+  BelowPrompt,
+  // Bottom of the screen, displayed in every mode.
+  Bottom,
+}
+
+impl FromStr for Region {
+  type Err = String;
+
+  fn from_str(value: &str) -> Result<Region, String> {
+    match value {
+      "top" => Ok(Region::Top),
+      "above-prompt" => Ok(Region::AbovePrompt),
+      "below-prompt" => Ok(Region::BelowPrompt),
+      "bottom" => Ok(Region::Bottom),
+
+      _ => Err(format!("unknown region '{value}', must be one of top, above-prompt, below-prompt or bottom")),
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Widget {
+  // Large clock, in the configured font, with `--time`.
+  Clock,
+  // Current date, with `--time`, in the locale's format unless `--date-format`
+  // is given.
+  Date,
+  // Static text, with `--greeting`.
+  Greeting,
+  // Contents of the issue file, with `--issue`.
+  Issue,
+  // Text from the fortune source.
+  Fortune,
+  // Name of the host.
+  Hostname,
+  // Text avatar of the user being authenticated.
+  Avatar,
+  // Output of `--widget-command`.
+  Command,
+  // Key hints and state of the session, layout and locks.
+  Status,
+}
+
+impl FromStr for Widget {
+  type Err = String;
+
+  fn from_str(value: &str) -> Result<Widget, String> {
+    match value {
+      "clock" => Ok(Widget::Clock),
+      "date" => Ok(Widget::Date),
+      "greeting" => Ok(Widget::Greeting),
+      "issue" => Ok(Widget::Issue),
+      "fortune" => Ok(Widget::Fortune),
+      "hostname" => Ok(Widget::Hostname),
+      "avatar" => Ok(Widget::Avatar),
+      "command" => Ok(Widget::Command),
+      "status" => Ok(Widget::Status),
+
+      _ => Err(format!(
+        "unknown widget '{value}', must be one of clock, date, greeting, issue, fortune, hostname, avatar, command or status"
+      )),
+    }
+  }
+}
+
+// Widget displayed in a region, and how it is aligned horizontally.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Placement {
+  pub widget: Widget,
+  pub alignment: Alignment,
+}
+
+impl FromStr for Placement {
+  type Err = String;
+
+  // Parses a placement from its `WIDGET[:ALIGNMENT]` representation.
+  fn from_str(value: &str) -> Result<Placement, String> {
+    let (widget, alignment) = match value.split_once(':') {
+      Some((widget, alignment)) => (widget, Some(alignment)),
+      None => (value, None),
+    };
+
+    let alignment = match alignment {
+      None | Some("center") => Alignment::Center,
+      Some("left") => Alignment::Left,
+      Some("right") => Alignment::Right,
+      Some(alignment) => return Err(format!("unknown alignment '{alignment}', must be one of left, center or right")),
+    };
+
+    Ok(Placement {
+      widget: widget.parse()?,
+      alignment,
+    })
+  }
+}
+
+// Widgets displayed in each region of the screen, from top to bottom.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WidgetLayout {
+  pub top: Vec<Placement>,
+  pub above_prompt: Vec<Placement>,
+  pub below_prompt: Vec<Placement>,
+  pub bottom: Vec<Placement>,
+}
+
+impl Default for WidgetLayout {
+  fn default() -> WidgetLayout {
+    let centered = |widget| Placement {
+      widget,
+      alignment: Alignment::Center,
+    };
+
+    WidgetLayout {
+      top: vec![],
+      above_prompt: vec![
+        centered(Widget::Date),
+        centered(Widget::Clock),
+        centered(Widget::Issue),
+        centered(Widget::Greeting),
+      ],
+      below_prompt: vec![centered(Widget::Fortune)],
+      bottom: vec![centered(Widget::Status)],
+    }
+  }
+}
+
+impl WidgetLayout {
+  // Replaces the widgets of a region from a `REGION=WIDGET[:ALIGNMENT][,...]`
+  // directive, or empties it with `REGION=none`. Widgets are moved out of the
+  // region they were previously displayed in.
+  pub fn set(&mut self, directive: &str) -> Result<(), String> {
+    let (region, widgets) = directive
+      .split_once('=')
+      .ok_or("must be REGION=WIDGET[:ALIGNMENT][,WIDGET[:ALIGNMENT]...]")?;
+
+    let region = region.trim().parse::<Region>()?;
+
+    let placements: Vec<Placement> = match widgets.trim() {
+      "none" => vec![],
+      widgets => widgets.split(',').map(|widget| widget.trim().parse()).collect::<Result<_, _>>()?,
+    };
+
+    for (index, placement) in placements.iter().enumerate() {
+      if placements[..index].iter().any(|other| other.widget == placement.widget) {
+        return Err(format!("widget '{}' is listed more than once", widgets.split(',').nth(index).unwrap_or_default().trim()));
+      }
+
+      if placement.widget == Widget::Status && !matches!(region, Region::Top | Region::Bottom) {
+        return Err("the status bar can only be displayed at the top or the bottom of the screen".to_string());
+      }
+    }
+
+    for other in [&mut self.top, &mut self.above_prompt, &mut self.below_prompt, &mut self.bottom] {
+      other.retain(|existing| !placements.iter().any(|placement| placement.widget == existing.widget));
+    }
+
+    *self.region_mut(region) = placements;
+
+    Ok(())
+  }
+
+  pub fn region(&self, region: Region) -> &[Placement] {
+    match region {
+      Region::Top => &self.top,
+      Region::AbovePrompt => &self.above_prompt,
+      Region::BelowPrompt => &self.below_prompt,
+      Region::Bottom => &self.bottom,
+    }
+  }
+
+  pub fn contains(&self, widget: Widget) -> bool {
+    [&self.top, &self.above_prompt, &self.below_prompt, &self.bottom]
+      .iter()
+      .any(|placements| placements.iter().any(|placement| placement.widget == widget))
+  }
+
+  fn region_mut(&mut self, region: Region) -> &mut Vec<Placement> {
+    match region {
+      Region::Top => &mut self.top,
+      Region::AbovePrompt => &mut self.above_prompt,
+      Region::BelowPrompt => &mut self.below_prompt,
+      Region::Bottom => &mut self.bottom,
+    }
+  }
+}
+
+// Whether a widget's lines are wrapped to the width of the screen. Clocks and
+// avatars are drawings, which would be mangled by wrapping.
+pub fn wraps(widget: Widget) -> bool {
+  !matches!(widget, Widget::Clock | Widget::Avatar)
+}
+
+// Computes the text displayed by a widget, or `None` if it has nothing to
+// display. `height` is the height of the terminal, which a fortune must fit in.
+pub fn content(greeter: &mut Greeter, widget: Widget, width: u16, height: u16) -> Option<Text<'static>> {
+  let text = match widget {
+    Widget::Clock | Widget::Date if !greeter.settings.time => return None,
+
+    Widget::Clock => {
+      let rows = greeter.clock.get(&greeter.settings, greeter.locale);
+
+      Text::from(rows.iter().map(|row| Line::from(row.clone())).collect::<Vec<_>>())
+    }
+
+    Widget::Date => {
+      let format = match greeter.settings.date_format {
+        Some(ref format) => Cow::Borrowed(format.as_str()),
+        None => Cow::Owned(fl!("date")),
+      };
+
+      Text::from(Local::now().format_localized(&format, greeter.locale).to_string())
+    }
+
+    Widget::Greeting => Text::from(greeter.settings.greeting.clone()?),
+    Widget::Issue => ansi_text(&greeter.issue.as_ref()?.render()),
+
+    Widget::Fortune => {
+      let fortune = ansi_text(&greeter.fortune);
+
+      match Paragraph::new(fortune.clone()).wrap(Wrap { trim: false }).line_count(width) > height as usize {
+        true => Text::raw("Fortune too long, unfortunately"),
+        false => fortune,
+      }
+    }
+
+    Widget::Hostname => Text::from(greeter.hostname.clone()),
+    Widget::Avatar => avatar(greeter)?,
+    Widget::Command => ansi_text(&greeter.widget_output),
+    Widget::Status => return None,
+  };
+
+  match text.lines.iter().all(|line| line.spans.iter().all(|span| span.content.trim().is_empty())) {
+    true => None,
+    false => Some(text),
+  }
+}
+
+// Draws a widget, other than the status bar, in the area that was computed for
+// it.
+pub fn draw(greeter: &Greeter, f: &mut Frame, placed: &Placed) {
+  let Some(ref text) = placed.text else {
+    return;
+  };
+
+  let themed = match placed.widget {
+    Widget::Clock => Themed::Clock,
+    Widget::Date => Themed::Date,
+    Widget::Greeting | Widget::Issue | Widget::Fortune => Themed::Greet,
+    Widget::Hostname => Themed::Title,
+    _ => Themed::Text,
+  };
+
+  let mut paragraph = Paragraph::new(text.clone()).style(greeter.settings.theme.of(&[themed]));
+
+  if wraps(placed.widget) {
+    paragraph = paragraph.wrap(Wrap { trim: false });
+  }
+
+  f.render_widget(paragraph, placed.area.intersection(f.area()));
+}
+
+fn ansi_text(text: &str) -> Text<'static> {
+  let text = text.trim_end().replace('\t', "    ");
+
+  match text.into_text() {
+    Ok(text) => text,
+    Err(_) => Text::raw(text),
+  }
+}
+
+// Text avatar of the user whose password is being asked for, read from the
+// avatar directory, or their initials in a box.
+fn avatar(greeter: &Greeter) -> Option<Text<'static>> {
+  if greeter.mode != Mode::Password || greeter.username.value.is_empty() {
+    return None;
+  }
+
+  let username = greeter.username.value.as_str();
+
+  if !username.contains('/') {
+    if let Ok(avatar) = fs::read_to_string(format!("{AVATAR_DIR}/{username}")) {
+      let mut text = ansi_text(&avatar);
+      text.lines.truncate(AVATAR_HEIGHT);
+
+      return Some(text);
+    }
+  }
+
+  let initials: String = greeter
+    .username
+    .get()
+    .split_whitespace()
+    .filter_map(|word| word.chars().next())
+    .take(2)
+    .flat_map(char::to_uppercase)
+    .collect();
+
+  let width = display_width(&initials) as usize + 2;
+
+  Some(Text::from(vec![
+    Line::from(format!("╭{}╮", "─".repeat(width))),
+    Line::from(format!("│ {initials} │")),
+    Line::from(format!("╰{}╯", "─".repeat(width))),
+  ]))
+}
+
+// Horizontal position of a block of text in an area, from its alignment.
+pub fn align(area: Rect, width: u16, alignment: Alignment) -> Rect {
+  let width = width.min(area.width);
+
+  let x = match alignment {
+    Alignment::Left => area.x,
+    Alignment::Center => area.x + (area.width - width) / 2,
+    Alignment::Right => area.right() - width,
+  };
+
+  Rect::new(x, area.y, width, area.height)
+}
+
+#[cfg(test)]
+mod tests {
+  use tui::layout::Alignment;
+
+  use super::{content, Placement, Widget, WidgetLayout};
+  use crate::Greeter;
+
+  #[test]
+  fn regions_are_replaced_and_widgets_moved() {
+    let mut layout = WidgetLayout::default();
+
+    layout.set("top=clock:left, hostname:right").unwrap();
+    layout.set("below-prompt=none").unwrap();
+
+    assert_eq!(
+      layout.top,
+      vec![
+        Placement {
+          widget: Widget::Clock,
+          alignment: Alignment::Left
+        },
+        Placement {
+          widget: Widget::Hostname,
+          alignment: Alignment::Right
+        },
+      ]
+    );
+    assert_eq!(
+      layout.above_prompt.iter().map(|placement| placement.widget).collect::<Vec<_>>(),
+      vec![Widget::Date, Widget::Issue, Widget::Greeting]
+    );
+    assert!(layout.below_prompt.is_empty());
+    assert!(layout.contains(Widget::Status));
+    assert!(!layout.contains(Widget::Fortune));
+  }
+
+  #[test]
+  fn invalid_directives() {
+    let mut layout = WidgetLayout::default();
+
+    for (directive, error) in [
+      ("clock", "must be REGION=WIDGET[:ALIGNMENT][,WIDGET[:ALIGNMENT]...]"),
+      ("side=clock", "unknown region 'side'"),
+      ("top=weather", "unknown widget 'weather'"),
+      ("top=clock:middle", "unknown alignment 'middle'"),
+      ("top=clock,date,clock:left", "widget 'clock:left' is listed more than once"),
+      ("above-prompt=status", "the status bar can only be displayed"),
+    ] {
+      let err = layout.set(directive).unwrap_err();
+
+      assert!(err.starts_with(error), "{}: {}", directive, err);
+    }
+
+    assert_eq!(layout, WidgetLayout::default());
+  }
+
+  #[test]
+  fn date_and_clock_need_the_time_option() {
+    let mut greeter = Greeter::default();
+
+    assert!(content(&mut greeter, Widget::Clock, 80, 24).is_none());
+    assert!(content(&mut greeter, Widget::Date, 80, 24).is_none());
+
+    greeter.settings.time = true;
+
+    assert!(content(&mut greeter, Widget::Clock, 80, 24).is_some());
+    assert!(content(&mut greeter, Widget::Date, 80, 24).is_some());
+  }
+}